@@ -38,8 +38,8 @@
 
             src = ./.;
 
-            buildInputs = with pkgs; [ openssl libxkbcommon glib pango gdk-pixbuf graphene gtk4 gtk4-layer-shell ];
-            nativeBuildInputs = with pkgs; [ pkg-config ];
+            buildInputs = with pkgs; [ openssl libxkbcommon glib pango gdk-pixbuf graphene gtk4 gtk4-layer-shell tesseract leptonica ];
+            nativeBuildInputs = with pkgs; [ pkg-config rustPlatform.bindgenHook ];
           };
         });
 
@@ -0,0 +1,351 @@
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use futures::stream::{self, StreamExt};
+
+use crate::output::JsonTranslation;
+use crate::placeholder::Protection;
+use crate::translator::{Backend, Translation};
+use crate::window::{translate_protected, translate_protected_detailed, TranslationWindowConfig};
+
+/// How the translations of a batch are written, one line per record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BatchFormat {
+    /// The record, or its file, and its translation separated by a tab, with the tabs, newlines
+    /// and backslashes escaped
+    #[default]
+    Tsv,
+    /// A JSON object like `--format json`, with the `file` of the source if it was given
+    Jsonl,
+}
+
+/// Text translated in a batch.
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// File the text was read from, if it was not a line of stdin.
+    pub file: Option<PathBuf>,
+    pub text: String,
+}
+
+/// Records of `input`, one per line, or separated by NUL characters if `nul`. A separator at
+/// the end does not start another record.
+pub fn split_records(input: &str, nul: bool) -> Vec<Record> {
+    let separator = if nul { '\0' } else { '\n' };
+    let input = input.strip_suffix(separator).unwrap_or(input);
+    if input.is_empty() {
+        return Vec::new();
+    }
+
+    input
+        .split(separator)
+        .map(|text| Record {
+            file: None,
+            text: if nul {
+                text.to_string()
+            } else {
+                text.trim_end_matches('\r').to_string()
+            },
+        })
+        .collect()
+}
+
+/// Translates `records` like the windows of `config`, `jobs` of them at the same time, and writes
+/// the translations to `output` in the order of the records as soon as they are done.
+///
+/// A record which fails to translate is written with its error, `false` is returned then. The
+/// errors go to stderr in TSV.
+pub async fn translate(
+    config: &TranslationWindowConfig,
+    backend: Backend,
+    records: &[Record],
+    jobs: usize,
+    format: BatchFormat,
+    output: &mut impl Write,
+) -> anyhow::Result<bool> {
+    let protection = Arc::new(Protection::load(
+        config,
+        &config.from_lang,
+        &config.to_lang,
+    )?);
+    let config = Arc::new(config.clone());
+    translate_records(records, jobs, format, backend, output, move |text| {
+        let config = config.clone();
+        let protection = protection.clone();
+        async move {
+            let (from_lang, to_lang) = (&config.from_lang, &config.to_lang);
+            match format {
+                BatchFormat::Tsv => {
+                    translate_protected(&config, &protection, from_lang, to_lang, &text)
+                        .await
+                        .map(|text| Translation {
+                            text,
+                            ..Default::default()
+                        })
+                }
+                BatchFormat::Jsonl => {
+                    translate_protected_detailed(&config, &protection, from_lang, to_lang, &text)
+                        .await
+                }
+            }
+        }
+    })
+    .await
+}
+
+/// Writes the translations of `records` made by `translate` like [`translate`].
+async fn translate_records<F, T>(
+    records: &[Record],
+    jobs: usize,
+    format: BatchFormat,
+    backend: Backend,
+    output: &mut impl Write,
+    translate: F,
+) -> anyhow::Result<bool>
+where
+    F: Fn(String) -> T,
+    T: Future<Output = anyhow::Result<Translation>> + Send + 'static,
+{
+    // Only `jobs` records are translated ahead of the one written next, each in a task of its own
+    // so that the offline backend runs on several threads.
+    let mut translations = stream::iter(records)
+        .map(|record| {
+            // Empty lines stay empty, to keep the lines of a column in their rows.
+            let translation =
+                (!record.text.trim().is_empty()).then(|| translate(record.text.clone()));
+            tokio::spawn(async move {
+                let start = Instant::now();
+                match translation {
+                    Some(translation) => (translation.await, start.elapsed()),
+                    None => (Ok(Translation::default()), Duration::ZERO),
+                }
+            })
+        })
+        .buffered(jobs.max(1));
+
+    let mut succeeded = true;
+    for (number, record) in records.iter().enumerate() {
+        let task = translations
+            .next()
+            .await
+            .expect("There needs to be a translation for every record.");
+        let (translated, latency) = match task {
+            Ok((translated, latency)) => (translated, latency),
+            Err(err) => (
+                Err(err).context("The translation task panicked"),
+                Duration::ZERO,
+            ),
+        };
+        succeeded &= translated.is_ok();
+
+        let line = match format {
+            BatchFormat::Tsv => {
+                let source = match &record.file {
+                    Some(file) => file.display().to_string(),
+                    None => record.text.clone(),
+                };
+                let translation = match &translated {
+                    Ok(translation) => translation.text.trim_end_matches('\n'),
+                    Err(err) => {
+                        match &record.file {
+                            Some(file) => eprintln!("Error: {}: {:#}", file.display(), err),
+                            None => eprintln!("Error: record {}: {:#}", number + 1, err),
+                        }
+                        ""
+                    }
+                };
+                format!("{}\t{}", escape(&source), escape(translation))
+            }
+            BatchFormat::Jsonl => serde_json::to_string(&JsonTranslation {
+                file: record.file.as_deref(),
+                ..JsonTranslation::new(&record.text, &translated, backend, latency)
+            })?,
+        };
+        writeln!(output, "{}", line).context("Failed to write the translations")?;
+        output.flush().context("Failed to write the translations")?;
+    }
+
+    Ok(succeeded)
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn texts(records: &[Record]) -> Vec<&str> {
+        records.iter().map(|record| record.text.as_str()).collect()
+    }
+
+    fn records(texts: &[&str]) -> Vec<Record> {
+        texts
+            .iter()
+            .map(|text| Record {
+                file: None,
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_records_of_lines() {
+        let records = split_records("one\ntwo\r\n\nthree", false);
+
+        assert_eq!(texts(&records), ["one", "two", "", "three"]);
+        assert!(records.iter().all(|record| record.file.is_none()));
+    }
+
+    #[test]
+    fn split_records_trailing_separator() {
+        assert_eq!(
+            texts(&split_records("one\r\ntwo\r\n", false)),
+            ["one", "two"]
+        );
+        assert_eq!(texts(&split_records("one\n\n", false)), ["one", ""]);
+        assert!(split_records("", false).is_empty());
+        assert!(split_records("\n", false).is_empty());
+    }
+
+    #[test]
+    fn split_records_nul() {
+        let records = split_records("one\ntwo\r\n\0three\0", true);
+
+        assert_eq!(texts(&records), ["one\ntwo\r\n", "three"]);
+        assert!(split_records("\0", true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn translations_in_order() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        // The first records take the longest, so they are done last.
+        let records = records(&["60", "40", "", "0", "20", "10"]);
+        let mut output = Vec::new();
+
+        let succeeded = translate_records(
+            &records,
+            2,
+            BatchFormat::Tsv,
+            Backend::Google,
+            &mut output,
+            |text| {
+                let (running, most_running) = (running.clone(), most_running.clone());
+                async move {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_running.fetch_max(now_running, Ordering::SeqCst);
+                    let millis = text.parse().unwrap();
+                    tokio::time::sleep(Duration::from_millis(millis)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(Translation {
+                        text: format!("{} ms\n", text),
+                        ..Default::default()
+                    })
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(succeeded);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "60\t60 ms\n40\t40 ms\n\t\n0\t0 ms\n20\t20 ms\n10\t10 ms\n"
+        );
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn tsv_with_failures() {
+        let records = records(&["a\\b", "fail\tnow", "c"]);
+        let mut output = Vec::new();
+
+        let succeeded = translate_records(
+            &records,
+            4,
+            BatchFormat::Tsv,
+            Backend::Google,
+            &mut output,
+            |text| async move {
+                if text.starts_with("fail") {
+                    return Err(anyhow::anyhow!("No network"));
+                }
+                Ok(Translation {
+                    text: format!("{}\n{}", text, text),
+                    ..Default::default()
+                })
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!succeeded);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "a\\\\b\ta\\\\b\\na\\\\b\nfail\\tnow\t\nc\tc\\nc\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn jsonl() {
+        let records = vec![
+            Record {
+                file: Some(PathBuf::from("notes/a.txt")),
+                text: "Hallo".to_string(),
+            },
+            Record {
+                file: None,
+                text: "fail".to_string(),
+            },
+        ];
+        let mut output = Vec::new();
+
+        translate_records(
+            &records,
+            4,
+            BatchFormat::Jsonl,
+            Backend::Llm,
+            &mut output,
+            |text| async move {
+                if text == "fail" {
+                    return Err(anyhow::anyhow!("No network"));
+                }
+                Ok(Translation {
+                    text: "Hello".to_string(),
+                    detected_lang: Some("de".to_string()),
+                    alternatives: vec!["Hi".to_string()],
+                })
+            },
+        )
+        .await
+        .unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["file"], "notes/a.txt");
+        assert_eq!(lines[0]["source"], "Hallo");
+        assert_eq!(lines[0]["result"], "Hello");
+        assert_eq!(lines[0]["detected_lang"], "de");
+        assert_eq!(lines[0]["alternatives"], serde_json::json!(["Hi"]));
+        assert_eq!(lines[0]["backend"], "llm");
+        assert!(lines[0]["error"].is_null());
+        assert!(lines[1].get("file").is_none());
+        assert!(lines[1]["result"].is_null());
+        assert_eq!(lines[1]["error"], "No network");
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{anyhow, Context};
+use smithay_client_toolkit::{
+    delegate_shm,
+    shm::{slot::SlotPool, Shm, ShmHandler},
+};
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_output::WlOutput, wl_registry::WlRegistry, wl_shm},
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+};
+
+use crate::env_info::select_region;
+
+/// Rectangle of an output, in surface coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    /// Rectangle covering the pixels from `start` to `end` included, whichever way it was dragged,
+    /// clamped to a `output_width`x`output_height` output.
+    ///
+    /// A click without a drag, `start` being `end`, selects nothing.
+    pub fn from_corners(
+        output_width: i32,
+        output_height: i32,
+        start: (i32, i32),
+        end: (i32, i32),
+    ) -> Self {
+        if start == end {
+            return Self {
+                x: start.0,
+                y: start.1,
+                width: 0,
+                height: 0,
+            };
+        }
+        let (left, right) = span(output_width, start.0, end.0);
+        let (top, bottom) = span(output_height, start.1, end.1);
+
+        Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub fn contains(self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// Whether `(x, y)` is inside the region but within `width` pixels of its edge.
+    pub fn on_border(self, x: i32, y: i32, width: i32) -> bool {
+        self.contains(x, y)
+            && !(x >= self.x + width
+                && x < self.x + self.width - width
+                && y >= self.y + width
+                && y < self.y + self.height - width)
+    }
+}
+
+/// Start and end, exclusive, of the pixels between `a` and `b` included, within `0..length`.
+fn span(length: i32, a: i32, b: i32) -> (i32, i32) {
+    let length = length.max(0);
+    (
+        a.min(b).clamp(0, length),
+        (a.max(b).saturating_add(1)).clamp(0, length),
+    )
+}
+
+/// Captured pixels, 3 bytes per pixel in RGB order, row after row.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BufferInfo {
+    format: wl_shm::Format,
+    width: i32,
+    height: i32,
+    stride: i32,
+}
+
+struct State {
+    shm: Shm,
+    buffer_info: Option<BufferInfo>,
+    buffer_done: bool,
+    y_invert: bool,
+    ready: bool,
+    failed: bool,
+}
+
+/// Lets the user select a region of the screen and captures it, `None` if cancelled.
+pub fn capture_region() -> anyhow::Result<Option<Frame>> {
+    select_region(capture)
+}
+
+/// Copies `region` of `output` through wlr-screencopy.
+fn capture(conn: &Connection, output: &WlOutput, region: Region) -> anyhow::Result<Frame> {
+    let (globals, mut event_queue) = registry_queue_init::<State>(conn)?;
+    let qh = event_queue.handle();
+
+    let manager = globals
+        .bind::<ZwlrScreencopyManagerV1, _, _>(&qh, 1..=3, ())
+        .context("The compositor does not support wlr-screencopy")?;
+    let mut state = State {
+        shm: Shm::bind(&globals, &qh).context("wl_shm is not available")?,
+        buffer_info: None,
+        buffer_done: false,
+        y_invert: false,
+        ready: false,
+        failed: false,
+    };
+
+    let frame = manager.capture_output_region(
+        0,
+        output,
+        region.x,
+        region.y,
+        region.width,
+        region.height,
+        &qh,
+        (),
+    );
+    // Version 3 announces every supported buffer type then sends `buffer_done`, the older ones
+    // only announce the shm buffer.
+    while !state.failed
+        && (state.buffer_info.is_none() || frame.version() >= 3 && !state.buffer_done)
+    {
+        event_queue.blocking_dispatch(&mut state)?;
+    }
+    let info = match state.buffer_info {
+        Some(info) if !state.failed => info,
+        _ => return Err(anyhow!("The compositor failed to capture the screen")),
+    };
+
+    let mut pool = SlotPool::new((info.stride * info.height) as usize, &state.shm)?;
+    let (buffer, _) = pool.create_buffer(info.width, info.height, info.stride, info.format)?;
+    frame.copy(buffer.wl_buffer());
+    while !state.ready && !state.failed {
+        event_queue.blocking_dispatch(&mut state)?;
+    }
+    frame.destroy();
+    manager.destroy();
+    if state.failed {
+        return Err(anyhow!("The compositor failed to capture the screen"));
+    }
+
+    let pixels = buffer
+        .canvas(&mut pool)
+        .context("The captured buffer is still in use")?;
+    let data = to_rgb(
+        pixels,
+        info.format,
+        info.width,
+        info.height,
+        info.stride,
+        state.y_invert,
+    )?;
+
+    Ok(Frame {
+        data,
+        width: info.width,
+        height: info.height,
+    })
+}
+
+/// Converts `width`x`height` pixels in the 32 bits `format` to RGB, flipping them if `y_invert`.
+fn to_rgb(
+    pixels: &[u8],
+    format: wl_shm::Format,
+    width: i32,
+    height: i32,
+    stride: i32,
+    y_invert: bool,
+) -> anyhow::Result<Vec<u8>> {
+    // Byte order of red, green and blue in memory, the formats are little-endian.
+    let [r, g, b] = match format {
+        wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888 => [2, 1, 0],
+        wl_shm::Format::Abgr8888 | wl_shm::Format::Xbgr8888 => [0, 1, 2],
+        format => return Err(anyhow!("Unsupported screen capture format {:?}", format)),
+    };
+    let (width, height, stride) = (width as usize, height as usize, stride as usize);
+    if stride < width * 4 || pixels.len() < stride * height {
+        return Err(anyhow!("The screen capture buffer is too small"));
+    }
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for row in 0..height {
+        let row = if y_invert { height - 1 - row } else { row };
+        for pixel in pixels[row * stride..][..width * 4].as_chunks::<4>().0 {
+            rgb.extend_from_slice(&[pixel[r], pixel[g], pixel[b]]);
+        }
+    }
+
+    Ok(rgb)
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                state.buffer_info = Some(BufferInfo {
+                    format,
+                    width: width as _,
+                    height: height as _,
+                    stride: stride as _,
+                })
+            }
+            zwlr_screencopy_frame_v1::Event::BufferDone => state.buffer_done = true,
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => state.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert),
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => state.ready = true,
+            zwlr_screencopy_frame_v1::Event::Failed => state.failed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wayland_client::protocol::wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_shm!(State);
+delegate_noop!(State: ZwlrScreencopyManagerV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: i32, y: i32, width: i32, height: i32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn from_corners_whichever_way_it_was_dragged() {
+        let dragged = region(10, 20, 91, 31);
+        assert_eq!(
+            Region::from_corners(1920, 1080, (10, 20), (100, 50)),
+            dragged
+        );
+        assert_eq!(
+            Region::from_corners(1920, 1080, (100, 50), (10, 20)),
+            dragged
+        );
+        assert_eq!(
+            Region::from_corners(1920, 1080, (10, 50), (100, 20)),
+            dragged
+        );
+    }
+
+    #[test]
+    fn from_corners_clamped_to_the_output() {
+        assert_eq!(
+            Region::from_corners(1920, 1080, (-10, -20), (2000, 1100)),
+            region(0, 0, 1920, 1080)
+        );
+        assert!(Region::from_corners(1920, 1080, (2000, 10), (2100, 100)).is_empty());
+    }
+
+    #[test]
+    fn single_click_is_empty() {
+        assert!(Region::from_corners(1920, 1080, (10, 20), (10, 20)).is_empty());
+        assert!(!Region::from_corners(1920, 1080, (10, 20), (11, 20)).is_empty());
+    }
+
+    #[test]
+    fn on_border() {
+        let region = region(10, 10, 20, 20);
+        assert!(region.on_border(10, 10, 2));
+        assert!(region.on_border(11, 20, 2));
+        assert!(region.on_border(29, 29, 2));
+        assert!(region.on_border(20, 28, 2));
+        assert!(!region.on_border(12, 12, 2));
+        assert!(!region.on_border(20, 20, 2));
+        assert!(!region.on_border(9, 20, 2));
+        assert!(!region.on_border(30, 20, 2));
+    }
+
+    /// 2x2 pixels, red and green then blue and white, in `[b, g, r, x]` order, with 4 bytes of
+    /// padding at the end of the rows.
+    const XRGB: &[u8] = &[
+        0, 0, 255, 0, 0, 255, 0, 0, 9, 9, 9, 9, //
+        255, 0, 0, 0, 255, 255, 255, 0, 9, 9, 9, 9,
+    ];
+    const RGB: &[u8] = &[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+
+    #[test]
+    fn to_rgb_from_xrgb_with_stride_padding() {
+        let rgb = to_rgb(XRGB, wl_shm::Format::Xrgb8888, 2, 2, 12, false).unwrap();
+        assert_eq!(rgb, RGB);
+    }
+
+    #[test]
+    fn to_rgb_from_xbgr() {
+        let xbgr: Vec<u8> = XRGB
+            .chunks(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect();
+        let rgb = to_rgb(&xbgr, wl_shm::Format::Xbgr8888, 2, 2, 12, false).unwrap();
+        assert_eq!(rgb, RGB);
+    }
+
+    #[test]
+    fn to_rgb_flips_the_rows_if_y_inverted() {
+        let rgb = to_rgb(XRGB, wl_shm::Format::Argb8888, 2, 2, 12, true).unwrap();
+        assert_eq!(rgb, [&RGB[6..], &RGB[..6]].concat());
+    }
+
+    #[test]
+    fn to_rgb_errors() {
+        assert!(to_rgb(XRGB, wl_shm::Format::Rgb565, 2, 2, 12, false).is_err());
+        assert!(to_rgb(XRGB, wl_shm::Format::Xrgb8888, 2, 2, 4, false).is_err());
+        assert!(to_rgb(&XRGB[..20], wl_shm::Format::Xrgb8888, 2, 2, 12, false).is_err());
+    }
+}
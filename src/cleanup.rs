@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+
+/// Fix for text copied from screenshots, code comments or terminals, applied before translating.
+///
+/// The steps always run in the order below, whatever the order they are given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanupStep {
+    /// Removes shell prompts like `$ `, `>>> ` or `user@host:~$ ` at the start of the lines.
+    StripPrompts,
+    /// Removes `//`, `#`, `*`, `/*` and `*/` around the lines.
+    StripCommentMarkers,
+    /// Joins the words hyphenated at the end of a line, before a lowercase letter. Compounds like
+    /// `well-known` broken after their hyphen are joined too, as `wellknown`.
+    Dehyphenate,
+    /// Joins the lines of each paragraph, paragraphs are separated by empty lines.
+    JoinLines,
+    /// Collapses the runs of spaces and of empty lines.
+    CollapseWhitespace,
+}
+
+pub fn clean(text: &str, steps: &[CleanupStep]) -> String {
+    let mut text = text.to_string();
+
+    if steps.contains(&CleanupStep::StripPrompts) {
+        text = replace_all(
+            &text,
+            r"(?m)^[ \t]*(?:[\w.-]+@[\w.-]+(?::\S*)?[ \t]*)?(?:\$|%|>>>|>|❯)[ \t]+",
+            "",
+        );
+    }
+    if steps.contains(&CleanupStep::StripCommentMarkers) {
+        // The closing markers first, the `*` of ` */` would be taken for a leading marker.
+        text = replace_all(&text, r"(?m)[ \t]*\*+/[ \t]*$", "");
+        text = replace_all(&text, r"(?m)^[ \t]*(?:/\*+|//+!?|\*+|#+)[ \t]?", "");
+    }
+    if steps.contains(&CleanupStep::Dehyphenate) {
+        text = replace_all(&text, r"(\p{L})-[ \t]*\r?\n[ \t]*(\p{Ll})", "$1$2");
+    }
+    if steps.contains(&CleanupStep::JoinLines) {
+        text = join_lines(&text);
+    }
+    if steps.contains(&CleanupStep::CollapseWhitespace) {
+        text = replace_all(&text, r"[ \t]+", " ");
+        text = replace_all(&text, r"(?m)^ | $", "");
+        text = replace_all(&text, r"\n{3,}", "\n\n");
+        text = text.trim().to_string();
+    }
+
+    text
+}
+
+/// Joins the lines of each paragraph with a space instead of the line breaks and the spaces
+/// around them, the empty lines between the paragraphs are kept.
+fn join_lines(text: &str) -> String {
+    let mut joined = String::new();
+    let mut in_paragraph = false;
+    for (index, line) in text.split('\n').enumerate() {
+        if in_paragraph && !line.trim().is_empty() {
+            joined.truncate(joined.trim_end_matches([' ', '\t', '\r']).len());
+            joined.push(' ');
+            joined.push_str(line.trim_start());
+            continue;
+        }
+        if index > 0 {
+            joined.push('\n');
+        }
+        joined.push_str(line);
+        in_paragraph = !line.trim().is_empty();
+    }
+
+    joined
+}
+
+fn replace_all(text: &str, pattern: &str, replacement: &str) -> String {
+    regex::Regex::new(pattern)
+        .expect("Cleanup regexes need to be valid.")
+        .replace_all(text, replacement)
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_prompts() {
+        let text = "$ cargo build\n>>> print(1)\nuser@host:~/src$ ls\n❯ git status\nkept $ here";
+        assert_eq!(
+            clean(text, &[CleanupStep::StripPrompts]),
+            "cargo build\nprint(1)\nls\ngit status\nkept $ here"
+        );
+    }
+
+    #[test]
+    fn strip_comment_markers() {
+        let text = "/**\n * Opens the\n * window.\n */\n// Line comment\n# Shell comment\n";
+        assert_eq!(
+            clean(text, &[CleanupStep::StripCommentMarkers]),
+            "\nOpens the\nwindow.\n\nLine comment\nShell comment\n"
+        );
+    }
+
+    #[test]
+    fn dehyphenate() {
+        assert_eq!(
+            clean(
+                "transla-\n  tion and Wayland-\nCompositor",
+                &[CleanupStep::Dehyphenate]
+            ),
+            "translation and Wayland-\nCompositor"
+        );
+        // Compounds are not told apart from hyphenated words.
+        assert_eq!(
+            clean("a well-\nknown fix", &[CleanupStep::Dehyphenate]),
+            "a wellknown fix"
+        );
+    }
+
+    #[test]
+    fn join_lines_of_each_paragraph() {
+        assert_eq!(
+            clean(
+                "This is  \n  a paragraph\nof three lines.\n\nAnother\none.",
+                &[CleanupStep::JoinLines]
+            ),
+            "This is a paragraph of three lines.\n\nAnother one."
+        );
+    }
+
+    #[test]
+    fn join_lines_of_one_character() {
+        assert_eq!(clean("a\nb\nc", &[CleanupStep::JoinLines]), "a b c");
+        assert_eq!(
+            clean("This is\nI\nthink", &[CleanupStep::JoinLines]),
+            "This is I think"
+        );
+    }
+
+    #[test]
+    fn join_lines_keeps_the_empty_lines_and_crlf() {
+        assert_eq!(
+            clean("a\r\nb\r\n  \r\nc\r\n", &[CleanupStep::JoinLines]),
+            "a b\r\n  \r\nc\r\n"
+        );
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        assert_eq!(
+            clean(
+                "  too   many\tspaces \n\n\n\nand lines  ",
+                &[CleanupStep::CollapseWhitespace]
+            ),
+            "too many spaces\n\nand lines"
+        );
+    }
+
+    #[test]
+    fn steps_run_in_order() {
+        let text = "// Some hyphen-\n// ated words\n// on two lines.";
+        assert_eq!(
+            clean(
+                text,
+                &[
+                    CleanupStep::CollapseWhitespace,
+                    CleanupStep::JoinLines,
+                    CleanupStep::Dehyphenate,
+                    CleanupStep::StripCommentMarkers,
+                ]
+            ),
+            "Some hyphenated words on two lines."
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::cleanup::CleanupStep;
+use crate::placement::Placement;
+use crate::size::{Dimensions, WindowSize};
+use crate::source::Source;
+use crate::translator::Backend;
+use crate::window::CopyTarget;
+
+const APP_DIR_NAME: &str = "wl_translation_window";
+
+/// Options read from the config file, every field can be overridden from the command line.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub source: Option<Source>,
+    pub ocr_lang: Option<String>,
+    pub cleanup: Option<Vec<CleanupStep>>,
+    pub placement: Option<Placement>,
+    pub cursor_offset: Option<i32>,
+    pub size: Option<WindowSize>,
+    pub min_size: Option<Dimensions>,
+    pub max_size: Option<Dimensions>,
+    pub close_on_focus_loss: Option<bool>,
+    /// In milliseconds.
+    pub close_on_leave: Option<u64>,
+    /// In milliseconds.
+    pub close_after: Option<u64>,
+    pub shortcuts: Shortcuts,
+    pub auto_copy: Option<CopyTarget>,
+    /// In milliseconds.
+    pub watch_delay: Option<u64>,
+    pub backend: Option<Backend>,
+    pub offline_model_dir: Option<PathBuf>,
+    pub llm: LlmConfig,
+    pub command: Option<String>,
+    /// In milliseconds.
+    pub command_timeout: Option<u64>,
+    /// An empty list disables the dictionary lookup.
+    pub dictionary_dirs: Option<Vec<PathBuf>>,
+    pub glossary_dir: Option<PathBuf>,
+    pub shield: Option<bool>,
+    pub markdown: Option<bool>,
+}
+
+/// Keyboard shortcuts of the translation window, in the syntax of `gtk_shortcut_trigger_parse_string`
+/// (e.g. `<Control>Return`, alternatives separated by `|`). An empty string disables one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Shortcuts {
+    pub close: String,
+    pub translate: String,
+    /// Copies the whole translation when no text is selected.
+    pub copy: String,
+    pub swap_languages: String,
+    pub focus_languages: String,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            close: "Escape".to_string(),
+            translate: "<Control>Return|<Control>KP_Enter".to_string(),
+            copy: "<Control>c".to_string(),
+            swap_languages: "<Control>s".to_string(),
+            focus_languages: "<Control>l".to_string(),
+        }
+    }
+}
+
+/// Options of `--backend llm`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LlmConfig {
+    /// Base URL of the OpenAI-compatible API, e.g. `http://localhost:11434/v1` for Ollama.
+    pub url: Option<String>,
+    /// Sent as a bearer token, defaults to `$OPENAI_API_KEY`.
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    /// System prompt, `{from}`, `{to}` and `{context}` are replaced by the languages and `context`.
+    pub prompt: Option<String>,
+    /// Extra instructions like the subject of the text or the tone of the translation.
+    pub context: Option<String>,
+}
+
+impl Config {
+    /// Loads the config from `path`, or from the default location if `path` is `None`.
+    ///
+    /// A missing file at the default location is not an error.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match config_dir() {
+                Some(dir) => (dir.join("config.toml"), false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/wl_translation_window`, falling back to `~/.config/wl_translation_window`.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// `$XDG_RUNTIME_DIR`, falling back to the temporary directory.
+pub fn runtime_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::temp_dir(),
+    }
+}
+
+/// `$XDG_STATE_HOME/wl_translation_window`, falling back to `~/.local/state/wl_translation_window`.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state").map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// `$XDG_DATA_HOME/wl_translation_window`, falling back to `~/.local/share/wl_translation_window`.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME))
+}
+
+pub fn xdg_dir(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(home_fallback)),
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use glib::clone;
+use gtk4::prelude::*;
+use gtk4::Application;
+
+use crate::config::runtime_dir;
+use crate::window::{TranslationWindow, TranslationWindowConfig};
+
+/// How long the client waits for the daemon to accept a window.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Socket of the daemon of the current user, named after the user since the temporary directory
+/// used without `$XDG_RUNTIME_DIR` is shared.
+pub fn socket_path() -> PathBuf {
+    runtime_dir().join(format!("wl_translation_window-{}.sock", current_uid()))
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and always succeeds.
+    unsafe { libc::getuid() }
+}
+
+/// User of the process at the other end of `stream`.
+fn peer_uid(stream: &UnixStream) -> anyhow::Result<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `credentials` is valid for writes of `len` bytes, which getsockopt does not exceed.
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error())
+            .context("Failed to get the user at the other end of the socket");
+    }
+
+    Ok(credentials.uid)
+}
+
+/// Asks the running daemon to open the window, returns `false` if there is no daemon.
+///
+/// The request is `config` as a single line of JSON, the daemon answers `ok` or an error message.
+pub fn send(config: &TranslationWindowConfig) -> anyhow::Result<bool> {
+    let mut stream = match UnixStream::connect(socket_path()) {
+        Ok(stream) => stream,
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::NotFound | ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(err) => return Err(err).context("Failed to connect to the daemon"),
+    };
+    // The text must not go to a socket put there by another user.
+    if peer_uid(&stream)? != current_uid() {
+        return Err(anyhow!(
+            "The daemon listening on {} belongs to another user",
+            socket_path().display()
+        ));
+    }
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut request = serde_json::to_string(config)?;
+    request.push('\n');
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .context("The daemon did not answer")?;
+    match response.trim_end() {
+        "ok" => Ok(true),
+        error => Err(anyhow!("The daemon refused the request: {}", error)),
+    }
+}
+
+/// Listens on [`socket_path`] and opens a window for every request, keeping `app` running.
+pub fn start_daemon(app: &Application) -> anyhow::Result<()> {
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        return Err(anyhow!(
+            "A daemon is already listening on {}",
+            path.display()
+        ));
+    }
+    // Left behind by a daemon which did not exit cleanly.
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if metadata.uid() != current_uid() {
+            return Err(anyhow!("{} belongs to another user", path.display()));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to make {} private", path.display()))?;
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = handle_client(stream, &sender) {
+                        eprintln!("Error: {:#}", err);
+                    }
+                }
+                Err(err) => eprintln!("Error: {:#}", err),
+            }
+        }
+    });
+
+    let hold = app.hold();
+    glib::spawn_future_local(clone!(@weak app => async move {
+        let _hold = hold;
+        while let Some(config) = receiver.recv().await {
+            TranslationWindow::new(&config).create(&app);
+        }
+    }));
+
+    Ok(())
+}
+
+fn handle_client(
+    stream: UnixStream,
+    sender: &tokio::sync::mpsc::UnboundedSender<TranslationWindowConfig>,
+) -> anyhow::Result<()> {
+    let uid = peer_uid(&stream)?;
+    if uid != current_uid() {
+        return Err(anyhow!("Refused a request of the user {}", uid));
+    }
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    let response = match serde_json::from_str::<TranslationWindowConfig>(&request) {
+        Ok(config) => {
+            sender.send(config)?;
+            "ok".to_string()
+        }
+        Err(err) => format!("invalid request: {}", err),
+    };
+    (&stream).write_all(format!("{}\n", response).as_bytes())?;
+
+    Ok(())
+}
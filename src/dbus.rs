@@ -0,0 +1,228 @@
+use glib::clone;
+use gtk4::prelude::*;
+use gtk4::Application;
+
+use crate::window::{tokio_runtime, translate_text, TranslationWindow, TranslationWindowConfig};
+use crate::APP_ID;
+
+const OBJECT_PATH: &str = "/com/github/plzfgme/wl_translation_window";
+const ERROR_TRANSLATION_FAILED: &str =
+    "com.github.plzfgme.wl_translation_window.Error.TranslationFailed";
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="com.github.plzfgme.wl_translation_window">
+    <method name="Translate">
+      <arg type="s" name="text" direction="in"/>
+      <arg type="s" name="from_lang" direction="in"/>
+      <arg type="s" name="to_lang" direction="in"/>
+      <arg type="s" name="translation" direction="out"/>
+    </method>
+    <method name="ShowPopup">
+      <arg type="s" name="text" direction="in"/>
+      <arg type="s" name="from_lang" direction="in"/>
+      <arg type="s" name="to_lang" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Owns [`APP_ID`] on the session bus and serves the `Translate` and `ShowPopup` methods, the
+/// popups use `config` with the text and languages of the call.
+pub fn start_dbus_service(app: &Application, config: TranslationWindowConfig) {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<TranslationWindowConfig>();
+
+    gio::bus_own_name(
+        gio::BusType::Session,
+        APP_ID,
+        gio::BusNameOwnerFlags::NONE,
+        move |connection, _| {
+            if let Err(err) = register_object(&connection, config.clone(), sender.clone()) {
+                eprintln!("Error: Failed to register the D-Bus object: {}", err);
+            }
+        },
+        |_, _| {},
+        |_, name| eprintln!("Error: Lost the D-Bus name {}", name),
+    );
+
+    let hold = app.hold();
+    glib::spawn_future_local(clone!(@weak app => async move {
+        let _hold = hold;
+        while let Some(config) = receiver.recv().await {
+            TranslationWindow::new(&config).create(&app);
+        }
+    }));
+}
+
+/// Serves the `Translate` and `ShowPopup` methods on `connection`, the popups to open are sent
+/// to `popups`.
+fn register_object(
+    connection: &gio::DBusConnection,
+    config: TranslationWindowConfig,
+    popups: tokio::sync::mpsc::UnboundedSender<TranslationWindowConfig>,
+) -> Result<gio::RegistrationId, glib::Error> {
+    let interface_info = gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .ok()
+        .and_then(|node_info| node_info.lookup_interface(APP_ID))
+        .expect("The D-Bus interface XML needs to be valid.");
+
+    connection.register_object(
+        OBJECT_PATH,
+        &interface_info,
+        move |_, _, _, _, method, parameters, invocation| {
+            let (text, from_lang, to_lang) = match parameters.get::<(String, String, String)>() {
+                Some(parameters) => parameters,
+                None => {
+                    invocation.return_dbus_error(
+                        "org.freedesktop.DBus.Error.InvalidArgs",
+                        "Expected (text, from_lang, to_lang)",
+                    );
+                    return;
+                }
+            };
+
+            match method {
+                "Translate" => translate(text, from_lang, to_lang, &config, invocation),
+                "ShowPopup" => {
+                    let _ = popups.send(TranslationWindowConfig {
+                        src_text: text,
+                        from_lang,
+                        to_lang,
+                        ..config.clone()
+                    });
+                    invocation.return_value(None);
+                }
+                _ => invocation.return_dbus_error(
+                    "org.freedesktop.DBus.Error.UnknownMethod",
+                    &format!("Unknown method {}", method),
+                ),
+            }
+        },
+        |_, _, _, _, _| ().to_variant(),
+        |_, _, _, _, _, _| false,
+    )
+}
+
+/// Translates on the tokio runtime like the windows of `config`, then answers `invocation` back
+/// on the main thread.
+fn translate(
+    text: String,
+    from_lang: String,
+    to_lang: String,
+    config: &TranslationWindowConfig,
+    invocation: gio::DBusMethodInvocation,
+) {
+    let config = config.clone();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    tokio_runtime().spawn(async move {
+        let translated = translate_text(&config, &from_lang, &to_lang, &text).await;
+        let _ = sender.send(translated);
+    });
+
+    glib::spawn_future_local(async move {
+        match receiver.await {
+            Ok(Ok(translation)) => invocation.return_value(Some(&(translation,).to_variant())),
+            Ok(Err(err)) => {
+                invocation.return_dbus_error(ERROR_TRANSLATION_FAILED, &format!("{:#}", err))
+            }
+            Err(_) => invocation
+                .return_dbus_error(ERROR_TRANSLATION_FAILED, "The translation was cancelled"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::translator::{AnyTranslator, CommandTranslator};
+
+    /// Private session bus run by `GTestDBus`, which gio 0.19 has no bindings for.
+    struct TestBus(*mut gio::ffi::GTestDBus);
+
+    impl TestBus {
+        fn up() -> Self {
+            // SAFETY: the bus is a new object owned by `TestBus`.
+            unsafe {
+                let bus = gio::ffi::g_test_dbus_new(gio::ffi::G_TEST_DBUS_NONE);
+                gio::ffi::g_test_dbus_up(bus);
+                Self(bus)
+            }
+        }
+
+        fn address(&self) -> String {
+            // SAFETY: the address of a running bus is a valid string owned by the bus.
+            unsafe {
+                CStr::from_ptr(gio::ffi::g_test_dbus_get_bus_address(self.0))
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            // SAFETY: the bus is up and only referenced by `TestBus`.
+            unsafe {
+                gio::ffi::g_test_dbus_down(self.0);
+                glib::gobject_ffi::g_object_unref(self.0.cast());
+            }
+        }
+    }
+
+    #[test]
+    fn translate_over_a_private_bus() {
+        // Upper-cases the text, and fails on the texts containing `fail`.
+        let translator = CommandTranslator::new(
+            "sh -c \"tr a-z A-Z | grep -v FAIL\"",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        // No other test uses the translator of the windows, so this one is the first to set it
+        // and does not reach Google Translate.
+        assert!(
+            crate::window::set_translator(AnyTranslator::Command(translator)),
+            "The translator of the windows needs to be set by this test only"
+        );
+        let args = crate::Args::try_parse_from(["test", "--from-lang", "en", "--to-lang", "fr"]);
+        let config = TranslationWindowConfig::from(args.unwrap());
+
+        let bus = TestBus::up();
+        let context = glib::MainContext::default();
+        let connection = gio::DBusConnection::for_address_sync(
+            &bus.address(),
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        )
+        .unwrap();
+        let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        let registration = register_object(&connection, config, sender).unwrap();
+
+        let call = |text: &str| {
+            context.block_on(connection.call_future(
+                connection.unique_name().as_deref(),
+                OBJECT_PATH,
+                APP_ID,
+                "Translate",
+                Some(&(text, "en", "fr").to_variant()),
+                Some(glib::VariantTy::new("(s)").unwrap()),
+                gio::DBusCallFlags::NONE,
+                5000,
+            ))
+        };
+
+        let reply = call("hello").unwrap();
+        assert_eq!(reply.get::<(String,)>().unwrap().0, "HELLO");
+
+        let err = call("fail").unwrap_err();
+        let remote_error = format!("GDBus.Error:{}:", ERROR_TRANSLATION_FAILED);
+        assert!(err.message().starts_with(&remote_error), "{}", err);
+
+        connection.unregister_object(registration).unwrap();
+    }
+}
@@ -0,0 +1,675 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+
+use crate::config::xdg_dir;
+
+/// Longest text looked up in the dictionaries, in words.
+const MAX_WORDS: usize = 3;
+const MAX_CHARS: usize = 64;
+/// How deep dictionaries are searched for in the dictionary directories.
+const MAX_DEPTH: usize = 3;
+
+/// Entry of a dictionary.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    /// Name of the dictionary.
+    pub dictionary: String,
+    pub word: String,
+    pub text: String,
+}
+
+/// Usual locations of StarDict and dictd dictionaries.
+pub fn default_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    dirs.extend(xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("stardict/dic")));
+    dirs.extend(std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".stardict/dic")));
+    dirs.push(PathBuf::from("/usr/share/stardict/dic"));
+    dirs.push(PathBuf::from("/usr/share/dictd"));
+
+    dirs
+}
+
+/// Word or short phrase of `text` worth looking up, without the punctuation around it.
+pub fn lookup_word(text: &str) -> Option<&str> {
+    let word = text
+        .trim()
+        .trim_matches(|c: char| matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | '"' | '(' | ')'));
+    let is_short = !word.is_empty()
+        && !word.contains('\n')
+        && word.chars().count() <= MAX_CHARS
+        && word.split_whitespace().count() <= MAX_WORDS;
+
+    is_short.then_some(word)
+}
+
+/// Looks `word` up, ignoring case, in the StarDict and dictd dictionaries found in `dirs`.
+///
+/// Dictionaries which cannot be read are reported on stderr and skipped.
+pub fn lookup(dirs: &[PathBuf], word: &str) -> Vec<Definition> {
+    let mut dictionaries = Vec::new();
+    for dir in dirs {
+        find_dictionaries(dir, MAX_DEPTH, &mut dictionaries);
+    }
+    dictionaries.sort();
+
+    let word = word.to_lowercase();
+    let mut definitions = Vec::new();
+    for path in dictionaries {
+        let found = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ifo") => lookup_stardict(&path, &word),
+            _ => lookup_dictd(&path, &word),
+        };
+        match found {
+            Ok(found) => definitions.extend(found),
+            Err(err) => eprintln!("Error: Failed to read {}: {:#}", path.display(), err),
+        }
+    }
+
+    definitions
+}
+
+/// Adds the `.ifo` files of StarDict dictionaries and the `.index` files of dictd dictionaries
+/// in `dir` to `dictionaries`.
+fn find_dictionaries(dir: &Path, depth: usize, dictionaries: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                find_dictionaries(&path, depth - 1, dictionaries);
+            }
+        } else if matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("ifo" | "index")
+        ) {
+            dictionaries.push(path);
+        }
+    }
+}
+
+fn lookup_stardict(ifo_path: &Path, word: &str) -> anyhow::Result<Vec<Definition>> {
+    let ifo = std::fs::read_to_string(ifo_path)?;
+    if ifo.lines().next().map(str::trim) != Some("StarDict's dict ifo file") {
+        return Err(anyhow!("Not a StarDict .ifo file"));
+    }
+    let option = |name: &str| {
+        ifo.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim().to_string())
+    };
+    let name = option("bookname").unwrap_or_else(|| file_stem(ifo_path));
+    let same_type_sequence = option("sametypesequence");
+    let offset_size = match option("idxoffsetbits").as_deref() {
+        Some("64") => 8,
+        _ => 4,
+    };
+
+    let index = match read_sibling(ifo_path, "idx") {
+        Ok(index) => index,
+        Err(_) => gunzip(&read_sibling(ifo_path, "idx.gz")?)?,
+    };
+    let mut data = DictData::open_sibling(ifo_path)?;
+
+    let mut definitions = Vec::new();
+    let mut rest = index.as_slice();
+    while let Some(end) = rest.iter().position(|byte| *byte == 0) {
+        let headword = String::from_utf8_lossy(&rest[..end]);
+        let numbers = rest
+            .get(end + 1..end + 1 + offset_size + 4)
+            .ok_or_else(|| anyhow!("Truncated .idx file"))?;
+        rest = &rest[end + 1 + offset_size + 4..];
+        if headword.to_lowercase() != word {
+            continue;
+        }
+
+        let offset = numbers[..offset_size]
+            .iter()
+            .fold(0, |offset, byte| (offset << 8) | u64::from(*byte));
+        let size = u32::from_be_bytes(numbers[offset_size..].try_into().unwrap());
+        let entry = data.read(offset, size as usize)?;
+        definitions.push(Definition {
+            dictionary: name.clone(),
+            word: headword.into_owned(),
+            text: stardict_text(&entry, same_type_sequence.as_deref()),
+        });
+    }
+
+    Ok(definitions)
+}
+
+/// Text of a StarDict entry, made of fields whose types are given by `same_type_sequence`, or
+/// prefixed with their type otherwise.
+fn stardict_text(entry: &[u8], same_type_sequence: Option<&str>) -> String {
+    let mut fields = Vec::new();
+    let mut rest = entry;
+    let mut types = same_type_sequence.map(|sequence| sequence.chars());
+    loop {
+        let field_type = match &mut types {
+            Some(types) => types.next(),
+            None => rest.split_first().map(|(field_type, tail)| {
+                rest = tail;
+                char::from(*field_type)
+            }),
+        };
+        let field_type = match field_type {
+            Some(field_type) if !rest.is_empty() => field_type,
+            _ => break,
+        };
+        // The last field of a sequence has no terminator nor size, it takes the rest of the entry.
+        let is_last = types
+            .as_ref()
+            .is_some_and(|types| types.as_str().is_empty());
+
+        let data = if field_type.is_ascii_lowercase() {
+            let end = if is_last {
+                rest.len()
+            } else {
+                rest.iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(rest.len())
+            };
+            let data = &rest[..end];
+            rest = rest.get(end + 1..).unwrap_or_default();
+            data
+        } else {
+            let (size, data) = if is_last {
+                (rest.len(), rest)
+            } else if rest.len() >= 4 {
+                let size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+                (size.min(rest.len() - 4), &rest[4..])
+            } else {
+                break;
+            };
+            rest = &data[size..];
+            &data[..size]
+        };
+
+        let text = String::from_utf8_lossy(data);
+        match field_type {
+            'm' | 'l' | 'y' => fields.push(text.trim().to_string()),
+            't' => fields.push(format!("[{}]", text.trim())),
+            'g' | 'h' | 'x' => fields.push(markup_to_text(&text)),
+            // Images, sounds and the other binary fields cannot be shown.
+            _ => {}
+        }
+    }
+
+    fields.retain(|field| !field.is_empty());
+    fields.join("\n")
+}
+
+fn lookup_dictd(index_path: &Path, word: &str) -> anyhow::Result<Vec<Definition>> {
+    let index = std::fs::read_to_string(index_path)?;
+    let name = file_stem(index_path);
+    let mut data = None;
+
+    let mut definitions = Vec::new();
+    for line in index.lines() {
+        let mut fields = line.split('\t');
+        let (headword, offset, size) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(headword), Some(offset), Some(size)) => (headword, offset, size),
+            _ => continue,
+        };
+        if headword.to_lowercase() != word {
+            continue;
+        }
+
+        let data = match &mut data {
+            Some(data) => data,
+            None => data.insert(DictData::open_sibling(index_path)?),
+        };
+        let entry = data.read(
+            decode_dictd_number(offset)?,
+            decode_dictd_number(size)? as usize,
+        )?;
+        definitions.push(Definition {
+            dictionary: name.clone(),
+            word: headword.to_string(),
+            text: String::from_utf8_lossy(&entry).trim().to_string(),
+        });
+    }
+
+    Ok(definitions)
+}
+
+/// Offsets and sizes of dictd indexes are written in base 64.
+fn decode_dictd_number(number: &str) -> anyhow::Result<u64> {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    number.bytes().try_fold(0u64, |value, digit| {
+        let digit = DIGITS
+            .iter()
+            .position(|known| *known == digit)
+            .ok_or_else(|| anyhow!("Invalid number `{}` in the .index file", number))?;
+        Ok((value << 6) | digit as u64)
+    })
+}
+
+/// Definitions of a dictionary, in a `.dict` file or in a `.dict.dz` file compressed with dictzip.
+enum DictData {
+    Plain(File),
+    /// Compressed in independent chunks, so that an entry can be read without decompressing
+    /// the whole file.
+    Dictzip {
+        file: File,
+        chunk_size: u64,
+        chunks: Chunks,
+    },
+    /// A `.dict.dz` file without the dictzip chunk table, decompressed at once.
+    Gzip(Vec<u8>),
+}
+
+/// Offset and size of the compressed chunks of a dictzip file.
+type Chunks = Vec<(u64, usize)>;
+
+impl DictData {
+    /// Opens the `.dict` or `.dict.dz` file next to `path`.
+    fn open_sibling(path: &Path) -> anyhow::Result<Self> {
+        if let Ok(file) = File::open(path.with_extension("dict")) {
+            return Ok(DictData::Plain(file));
+        }
+
+        let path = path.with_extension("dict.dz");
+        let mut file =
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut header = vec![0; 64 * 1024];
+        let len = file.read(&mut header)?;
+        header.truncate(len);
+
+        match dictzip_chunks(&header)? {
+            Some((chunk_size, chunks)) => Ok(DictData::Dictzip {
+                file,
+                chunk_size,
+                chunks,
+            }),
+            None => Ok(DictData::Gzip(gunzip(&std::fs::read(&path)?)?)),
+        }
+    }
+
+    fn read(&mut self, offset: u64, size: usize) -> anyhow::Result<Vec<u8>> {
+        match self {
+            DictData::Plain(file) => {
+                let mut entry = vec![0; size];
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut entry)?;
+                Ok(entry)
+            }
+            DictData::Dictzip {
+                file,
+                chunk_size,
+                chunks,
+            } => {
+                let first = (offset / *chunk_size) as usize;
+                let last = ((offset + size as u64).saturating_sub(1) / *chunk_size) as usize;
+                let mut decompressed = Vec::new();
+                for &(chunk_offset, chunk_len) in chunks
+                    .get(first..=last)
+                    .ok_or_else(|| anyhow!("Entry out of the .dict.dz file"))?
+                {
+                    let mut chunk = vec![0; chunk_len];
+                    file.seek(SeekFrom::Start(chunk_offset))?;
+                    file.read_exact(&mut chunk)?;
+                    let mut inflated = Vec::with_capacity(*chunk_size as usize);
+                    flate2::Decompress::new(false).decompress_vec(
+                        &chunk,
+                        &mut inflated,
+                        flate2::FlushDecompress::Sync,
+                    )?;
+                    decompressed.extend(inflated);
+                }
+
+                let start = (offset - first as u64 * *chunk_size) as usize;
+                decompressed
+                    .get(start..start + size)
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| anyhow!("Entry out of the .dict.dz file"))
+            }
+            DictData::Gzip(data) => data
+                .get(offset as usize..offset as usize + size)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| anyhow!("Entry out of the .dict.dz file")),
+        }
+    }
+}
+
+/// Chunk size and chunks of a dictzip file from its gzip `header`, `None` for a plain gzip file.
+fn dictzip_chunks(header: &[u8]) -> anyhow::Result<Option<(u64, Chunks)>> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    let truncated = || anyhow!("Truncated gzip header");
+    let u16_at = |at: usize| -> anyhow::Result<usize> {
+        let bytes = header.get(at..at + 2).ok_or_else(truncated)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    };
+
+    if header.get(..3) != Some(&[0x1f, 0x8b, 8]) {
+        return Err(anyhow!("Not a gzip file"));
+    }
+    let flags = header[3];
+    let mut position = 10;
+    let mut chunks = None;
+
+    if flags & FEXTRA != 0 {
+        let extra_end = position + 2 + u16_at(position)?;
+        position += 2;
+        while position + 4 <= extra_end {
+            let subfield = header.get(position..position + 2).ok_or_else(truncated)?;
+            let len = u16_at(position + 2)?;
+            if subfield == b"RA" {
+                let chunk_size = u16_at(position + 6)? as u64;
+                let count = u16_at(position + 8)?;
+                let sizes = (0..count)
+                    .map(|index| u16_at(position + 10 + index * 2))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                chunks = Some((chunk_size, sizes));
+            }
+            position += 4 + len;
+        }
+        position = extra_end;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = header[position.min(header.len())..]
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(truncated)?;
+            position += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        position += 2;
+    }
+
+    Ok(chunks.map(|(chunk_size, sizes)| {
+        let mut offset = position as u64;
+        let chunks = sizes
+            .into_iter()
+            .map(|size| {
+                let chunk = (offset, size);
+                offset += size as u64;
+                chunk
+            })
+            .collect();
+        (chunk_size, chunks)
+    }))
+}
+
+fn gunzip(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+
+    Ok(decompressed)
+}
+
+fn read_sibling(path: &Path, extension: &str) -> anyhow::Result<Vec<u8>> {
+    let path = path.with_extension(extension);
+    std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Plain text of HTML, XDXF or Pango markup.
+fn markup_to_text(markup: &str) -> String {
+    let text = regex::Regex::new(r"(?i)<br\s*/?>|</p>|</div>|</li>")
+        .expect("Markup regexes need to be valid.")
+        .replace_all(markup, "\n");
+    let text = regex::Regex::new(r"<[^>]*>")
+        .expect("Markup regexes need to be valid.")
+        .replace_all(&text, "");
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Compresses `data` like dictzip, in chunks of `chunk_size` bytes which can be decompressed
+    /// on their own.
+    fn dictzip(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut compress = flate2::Compress::new(flate2::Compression::default(), false);
+        let mut compressed = Vec::new();
+        let mut sizes = Vec::new();
+        let chunks: Vec<_> = data.chunks(chunk_size).collect();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let flush = if index + 1 == chunks.len() {
+                flate2::FlushCompress::Finish
+            } else {
+                flate2::FlushCompress::Full
+            };
+            let mut out = Vec::with_capacity(chunk.len() + 64);
+            compress.compress_vec(chunk, &mut out, flush).unwrap();
+            sizes.push(out.len() as u16);
+            compressed.extend(out);
+        }
+
+        // Magic, deflate, FEXTRA | FNAME, mtime, extra flags and OS.
+        let mut file = vec![0x1f, 0x8b, 8, 4 | 8, 0, 0, 0, 0, 0, 3];
+        let subfield_len = 6 + 2 * sizes.len() as u16;
+        file.extend((4 + subfield_len).to_le_bytes());
+        file.extend(b"RA");
+        file.extend(subfield_len.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.extend((chunk_size as u16).to_le_bytes());
+        file.extend((sizes.len() as u16).to_le_bytes());
+        for size in sizes {
+            file.extend(size.to_le_bytes());
+        }
+        file.extend(b"test.dict\0");
+        file.extend(compressed);
+        // The CRC and the size are not checked.
+        file.extend([0; 8]);
+        file
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Base 64 number of dictd indexes.
+    fn dictd_number(mut number: usize) -> String {
+        const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut digits = vec![DIGITS[number % 64]];
+        while number >= 64 {
+            number /= 64;
+            digits.insert(0, DIGITS[number % 64]);
+        }
+        String::from_utf8(digits).unwrap()
+    }
+
+    /// Writes a dictd dictionary `name` of `entries` in `dir`, its data compressed by `dict`.
+    fn write_dictd(dir: &Path, name: &str, entries: &[(&str, &str)], dict: fn(&[u8]) -> Vec<u8>) {
+        let mut data = Vec::new();
+        let mut index = String::new();
+        for (headword, definition) in entries {
+            index.push_str(&format!(
+                "{}\t{}\t{}\n",
+                headword,
+                dictd_number(data.len()),
+                dictd_number(definition.len())
+            ));
+            data.extend(definition.as_bytes());
+        }
+        std::fs::write(dir.join(format!("{}.index", name)), index).unwrap();
+        std::fs::write(dir.join(format!("{}.dict.dz", name)), dict(&data)).unwrap();
+    }
+
+    const ENTRIES: &[(&str, &str)] = &[
+        ("apple", "A round fruit of a tree of the rose family.\n"),
+        ("Bank", "The land alongside a river or lake.\n"),
+        (
+            "bank",
+            "An organization keeping the money of its customers.\n",
+        ),
+        ("cherry", "A small, round stone fruit.\n"),
+    ];
+
+    #[test]
+    fn dictd_numbers() {
+        assert_eq!(decode_dictd_number("A").unwrap(), 0);
+        assert_eq!(decode_dictd_number("B").unwrap(), 1);
+        assert_eq!(decode_dictd_number("a").unwrap(), 26);
+        assert_eq!(decode_dictd_number("/").unwrap(), 63);
+        assert_eq!(decode_dictd_number("BA").unwrap(), 64);
+        assert_eq!(decode_dictd_number("Bk/").unwrap(), 4096 + 36 * 64 + 63);
+        assert_eq!(decode_dictd_number("").unwrap(), 0);
+        assert!(decode_dictd_number("A-").is_err());
+    }
+
+    #[test]
+    fn dictzip_header() {
+        let file = dictzip(&[b'x'; 100], 40);
+
+        let (chunk_size, chunks) = dictzip_chunks(&file).unwrap().unwrap();
+
+        assert_eq!(chunk_size, 40);
+        assert_eq!(chunks.len(), 3);
+        // Header of 10 bytes, extra field of 2 + 4 + 6 + 3 * 2 bytes and the file name.
+        assert_eq!(chunks[0].0, 10 + 18 + 10);
+        assert_eq!(chunks[1].0, chunks[0].0 + chunks[0].1 as u64);
+        assert_eq!(chunks[2].0, chunks[1].0 + chunks[1].1 as u64);
+        assert_eq!(dictzip_chunks(&gzip(b"plain")).unwrap(), None);
+        assert!(dictzip_chunks(b"not gzip").is_err());
+    }
+
+    #[test]
+    fn dictd_dictzip_entries_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        // Small chunks, so that every entry spans several of them.
+        write_dictd(dir.path(), "words", ENTRIES, |data| dictzip(data, 16));
+        let index = dir.path().join("words.index");
+
+        for (headword, definition) in ENTRIES {
+            let definitions = lookup_dictd(&index, &headword.to_lowercase()).unwrap();
+            assert!(
+                definitions
+                    .iter()
+                    .any(|found| found.word == *headword && found.text == definition.trim()),
+                "{:?}",
+                definitions
+            );
+        }
+        let banks = lookup_dictd(&index, "bank").unwrap();
+        assert_eq!(banks.len(), 2);
+        assert!(banks.iter().all(|bank| bank.dictionary == "words"));
+    }
+
+    #[test]
+    fn dictd_plain_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        write_dictd(dir.path(), "words", ENTRIES, gzip);
+
+        let definitions = lookup_dictd(&dir.path().join("words.index"), "cherry").unwrap();
+
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].text, "A small, round stone fruit.");
+    }
+
+    #[test]
+    fn stardict_64_bit_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut data = Vec::new();
+        let mut index = Vec::new();
+        for (headword, definition) in ENTRIES {
+            index.extend(headword.as_bytes());
+            index.push(0);
+            index.extend((data.len() as u64).to_be_bytes());
+            index.extend((definition.len() as u32).to_be_bytes());
+            data.extend(definition.as_bytes());
+        }
+        std::fs::write(
+            dir.path().join("words.ifo"),
+            "StarDict's dict ifo file\nversion=3.0.0\nbookname=Words\nwordcount=4\n\
+             idxoffsetbits=64\nsametypesequence=m\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("words.idx.gz"), gzip(&index)).unwrap();
+        std::fs::write(dir.path().join("words.dict"), data).unwrap();
+
+        let definitions = lookup(&[dir.path().to_path_buf()], "BANK");
+
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(definitions[0].dictionary, "Words");
+        assert_eq!(definitions[0].word, "Bank");
+        assert_eq!(definitions[0].text, "The land alongside a river or lake.");
+        assert_eq!(definitions[1].word, "bank");
+    }
+
+    #[test]
+    fn stardict_truncated_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("words.ifo"),
+            "StarDict's dict ifo file\nbookname=Words\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("words.idx"), b"apple\0\0\0\0\0\0").unwrap();
+        std::fs::write(dir.path().join("words.dict"), b"").unwrap();
+
+        let err = lookup_stardict(&dir.path().join("words.ifo"), "apple").unwrap_err();
+
+        assert_eq!(err.to_string(), "Truncated .idx file");
+    }
+
+    #[test]
+    fn stardict_same_type_sequence() {
+        // The last field takes the rest of the entry, without a terminator.
+        assert_eq!(
+            stardict_text(b"ap\xc9\x99l\0A fruit.", Some("tm")),
+            "[ap\u{259}l]\nA fruit."
+        );
+        assert_eq!(
+            stardict_text(b"A fruit.\0more", Some("m")),
+            "A fruit.\0more"
+        );
+        assert_eq!(
+            stardict_text(b"<b>Fruit</b><br>round\0\x89PNG", Some("hP")),
+            "Fruit\nround"
+        );
+        // The sizes of the binary fields are given before their data but for the last one.
+        assert_eq!(
+            stardict_text(b"\0\0\0\x03PNGA fruit.", Some("Wm")),
+            "A fruit."
+        );
+    }
+
+    #[test]
+    fn stardict_typed_fields() {
+        assert_eq!(
+            stardict_text(b"tap\xc9\x99l\0mA fruit.\0W\0\0\0\x02ab", None),
+            "[ap\u{259}l]\nA fruit."
+        );
+    }
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            markup_to_text("<k>bank</k><br/>1. &lt;money&gt; &amp; more</p><p>2. river"),
+            "bank\n1. <money> & more\n2. river"
+        );
+    }
+}
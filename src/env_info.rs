@@ -1,11 +1,15 @@
+use anyhow::anyhow;
 use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
+    compositor::{CompositorHandler, CompositorState, SurfaceData},
     delegate_compositor, delegate_layer, delegate_output, delegate_pointer, delegate_registry,
     delegate_seat, delegate_shm,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
-    seat::{pointer::PointerHandler, Capability, SeatHandler, SeatState},
+    seat::{
+        pointer::{PointerEventKind, PointerHandler},
+        Capability, SeatHandler, SeatState,
+    },
     shell::{
         wlr_layer::{
             Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
@@ -17,11 +21,29 @@ use smithay_client_toolkit::{
 use wayland_client::{
     delegate_noop,
     globals::registry_queue_init,
-    protocol::{wl_buffer::WlBuffer, wl_pointer::WlPointer, wl_shm},
-    Connection, QueueHandle,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_keyboard::{self, WlKeyboard},
+        wl_output::WlOutput,
+        wl_pointer::WlPointer,
+        wl_shm,
+    },
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
 };
 
-#[derive(Debug)]
+use crate::capture::Region;
+
+/// Linux input event codes.
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const KEY_ESC: u32 = 1;
+
+/// Overlay colors when selecting a region, as premultiplied ARGB in memory order.
+const DIM_COLOR: [u8; 4] = [0, 0, 0, 0x60];
+const BORDER_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const BORDER_WIDTH: i32 = 1;
+
+#[derive(Debug, Clone, Copy)]
 pub struct EnvironmentInfo {
     pub monitor_width: i32,
     pub monitor_height: i32,
@@ -40,10 +62,60 @@ struct State {
     pointer: Option<WlPointer>,
     global_info: EnvironmentInfo,
 
+    /// Set when the user drags a region on the overlay instead of only giving the pointer position.
+    drag: Option<Drag>,
+    keyboard: Option<WlKeyboard>,
+    pool: Option<SlotPool>,
+    frame_pending: bool,
+    needs_redraw: bool,
+    cancelled: bool,
+
     exit: bool,
 }
 
+#[derive(Debug, Default)]
+struct Drag {
+    start: Option<(i32, i32)>,
+    end: (i32, i32),
+}
+
 pub fn collect_env_info() -> EnvironmentInfo {
+    let (_conn, state) = show_overlay(false);
+    state.global_info
+}
+
+/// Lets the user drag a rectangle on the overlay, left click to start and release to end, then
+/// hides the overlay and hands the rectangle and the output it is on to `capture`.
+///
+/// Returns `None` when cancelled with Escape, a right click, or a click without dragging.
+pub fn select_region<T>(
+    capture: impl FnOnce(&Connection, &WlOutput, Region) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<T>> {
+    let (conn, mut state) = show_overlay(true);
+    let region = match state.selected_region() {
+        Some(region) if !state.cancelled && !region.is_empty() => region,
+        _ => return Ok(None),
+    };
+    let output = state
+        .layer
+        .wl_surface()
+        .data::<SurfaceData>()
+        .and_then(|data| data.outputs().next())
+        .or_else(|| state.output_state.outputs().next())
+        .ok_or_else(|| anyhow!("Failed to find the output of the selected region"))?;
+
+    // Unmaps the overlay so that it is not captured.
+    state.layer.wl_surface().attach(None, 0, 0);
+    state.layer.commit();
+    state.pool.take();
+    conn.roundtrip()?;
+
+    capture(&conn, &output, region).map(Some)
+}
+
+/// Shows a transparent overlay over the whole output until the pointer enters it, or until a
+/// region is selected if `select`.
+fn show_overlay(select: bool) -> (Connection, State) {
     let conn = Connection::connect_to_env().unwrap();
 
     let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
@@ -75,6 +147,13 @@ pub fn collect_env_info() -> EnvironmentInfo {
             pointer_y: 0,
         },
 
+        drag: select.then(Drag::default),
+        keyboard: None,
+        pool: None,
+        frame_pending: false,
+        needs_redraw: false,
+        cancelled: false,
+
         exit: false,
     };
 
@@ -82,7 +161,7 @@ pub fn collect_env_info() -> EnvironmentInfo {
         event_queue.blocking_dispatch(&mut state).unwrap();
 
         if state.exit {
-            return state.global_info;
+            return (conn, state);
         }
     }
 }
@@ -92,10 +171,13 @@ impl State {
         let width = self.global_info.monitor_width;
         let height = self.global_info.monitor_height;
         let stride = width * 4;
+        let region = self.selected_region();
 
-        let mut pool = SlotPool::new((width * height * 4) as _, &self.shm).unwrap();
+        let pool = self
+            .pool
+            .get_or_insert_with(|| SlotPool::new((width * height * 4) as _, &self.shm).unwrap());
 
-        let (buffer, _) = pool
+        let (buffer, canvas) = pool
             .create_buffer(
                 width as _,
                 height as _,
@@ -103,6 +185,18 @@ impl State {
                 wl_shm::Format::Argb8888,
             )
             .unwrap();
+        if self.drag.is_some() {
+            for (index, pixel) in canvas.as_chunks_mut::<4>().0.iter_mut().enumerate() {
+                let x = index as i32 % width;
+                let y = index as i32 / width;
+                let color = match region {
+                    Some(region) if region.on_border(x, y, BORDER_WIDTH) => BORDER_COLOR,
+                    Some(region) if region.contains(x, y) => [0; 4],
+                    _ => DIM_COLOR,
+                };
+                *pixel = color;
+            }
+        }
 
         self.layer
             .wl_surface()
@@ -112,6 +206,28 @@ impl State {
             .frame(qh, self.layer.wl_surface().clone());
         buffer.attach_to(self.layer.wl_surface()).unwrap();
         self.layer.commit();
+        self.frame_pending = true;
+        self.needs_redraw = false;
+    }
+
+    /// Redraws the overlay now, or when the compositor is ready for a new frame.
+    fn request_redraw(&mut self, qh: &QueueHandle<Self>) {
+        self.needs_redraw = true;
+        if !self.frame_pending {
+            self.draw(qh);
+        }
+    }
+
+    fn selected_region(&self) -> Option<Region> {
+        let drag = self.drag.as_ref()?;
+        let start = drag.start?;
+
+        Some(Region::from_corners(
+            self.global_info.monitor_width,
+            self.global_info.monitor_height,
+            start,
+            drag.end,
+        ))
     }
 }
 
@@ -137,10 +253,14 @@ impl CompositorHandler for State {
     fn frame(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _surface: &wayland_client::protocol::wl_surface::WlSurface,
         _time: u32,
     ) {
+        self.frame_pending = false;
+        if self.needs_redraw && !self.exit {
+            self.draw(qh);
+        }
     }
 }
 
@@ -233,6 +353,9 @@ impl SeatHandler for State {
                 .expect("Failed to create pointer");
             self.pointer = Some(pointer);
         }
+        if capability == Capability::Keyboard && self.drag.is_some() && self.keyboard.is_none() {
+            self.keyboard = Some(seat.get_keyboard(qh, ()));
+        }
     }
 
     fn remove_capability(
@@ -245,6 +368,13 @@ impl SeatHandler for State {
         if capability == Capability::Pointer && self.pointer.is_some() {
             self.pointer.take();
         }
+        if capability == Capability::Keyboard {
+            if let Some(keyboard) = self.keyboard.take() {
+                if keyboard.version() >= 3 {
+                    keyboard.release();
+                }
+            }
+        }
     }
 
     fn remove_seat(
@@ -260,15 +390,66 @@ impl PointerHandler for State {
     fn pointer_frame(
         &mut self,
         _conn: &Connection,
-        _qh: &wayland_client::QueueHandle<Self>,
+        qh: &wayland_client::QueueHandle<Self>,
         _pointer: &WlPointer,
         events: &[smithay_client_toolkit::seat::pointer::PointerEvent],
     ) {
-        if let Some(event) = events.last() {
+        for event in events {
             self.global_info.pointer_x = event.position.0 as _;
             self.global_info.pointer_y = event.position.1 as _;
+            let position = (self.global_info.pointer_x, self.global_info.pointer_y);
+
+            let drag = match &mut self.drag {
+                Some(drag) => drag,
+                None => {
+                    self.exit = true;
+                    continue;
+                }
+            };
+            match event.kind {
+                PointerEventKind::Press {
+                    button: BTN_LEFT, ..
+                } => drag.start = Some(position),
+                PointerEventKind::Press {
+                    button: BTN_RIGHT, ..
+                } => {
+                    self.cancelled = true;
+                    self.exit = true;
+                }
+                PointerEventKind::Release {
+                    button: BTN_LEFT, ..
+                } if drag.start.is_some() => {
+                    self.cancelled = drag.start == Some(position);
+                    self.exit = true;
+                }
+                _ => {}
+            }
+            drag.end = position;
+        }
 
-            self.exit = true;
+        if self.drag.is_some() && !self.exit {
+            self.request_redraw(qh);
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for State {
+    fn event(
+        state: &mut Self,
+        _keyboard: &WlKeyboard,
+        event: wl_keyboard::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_keyboard::Event::Key {
+            key: KEY_ESC,
+            state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+            ..
+        } = event
+        {
+            state.cancelled = true;
+            state.exit = true;
         }
     }
 }
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::placeholder::Placeholders;
+
+/// Terms which must be translated a specific way, or not at all, for one language pair.
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+    /// Translation of each term, the term itself if it is left untranslated.
+    terms: HashMap<String, String>,
+    /// Matches any of the terms, the longest first.
+    regex: Option<regex::Regex>,
+}
+
+impl Glossary {
+    /// Loads the terms of `from` to `to` from `{from}-{to}.tsv` or `.csv` in `dir`, then those of
+    /// every pair from `all.tsv` or `all.csv`. Missing files are skipped.
+    ///
+    /// Each line holds a term and its translation, separated by a tab or a comma. Without a
+    /// translation the term is left as is. Empty lines and lines starting with `#` are ignored.
+    pub fn load(dir: &Path, from: &str, to: &str) -> anyhow::Result<Self> {
+        let mut terms = HashMap::new();
+        for name in [format!("{}-{}", from, to), "all".to_string()] {
+            for (extension, separator) in [("tsv", '\t'), ("csv", ',')] {
+                let path = dir.join(format!("{}.{}", name, extension));
+                let content = match std::fs::read_to_string(&path) {
+                    Ok(content) => content,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(err) => {
+                        return Err(err)
+                            .with_context(|| format!("Failed to read {}", path.display()))
+                    }
+                };
+
+                for (number, line) in content.lines().enumerate() {
+                    if line.trim().is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let fields = split_fields(line, separator);
+                    let term = fields[0].trim();
+                    if term.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "{}:{}: The term is empty",
+                            path.display(),
+                            number + 1
+                        ));
+                    }
+                    let translation = match fields.get(1).map(|field| field.trim()) {
+                        Some(translation) if !translation.is_empty() => translation,
+                        _ => term,
+                    };
+                    // The terms of the language pair come first and win over the others.
+                    terms
+                        .entry(term.to_string())
+                        .or_insert_with(|| translation.to_string());
+                }
+            }
+        }
+
+        let mut sources: Vec<&String> = terms.keys().collect();
+        sources.sort_by_key(|source| std::cmp::Reverse(source.len()));
+        let pattern = sources
+            .iter()
+            .map(|source| {
+                // Terms starting or ending with a letter of the Latin alphabet or a digit only
+                // match whole words, `Go` is not in `Google` but is in `用Go写`.
+                let start = if source.starts_with(is_word_char) {
+                    r"(?-u:\b)"
+                } else {
+                    ""
+                };
+                let end = if source.ends_with(is_word_char) {
+                    r"(?-u:\b)"
+                } else {
+                    ""
+                };
+                format!("{}{}{}", start, regex::escape(source), end)
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let regex = if sources.is_empty() {
+            None
+        } else {
+            Some(regex::Regex::new(&pattern).context("Failed to compile the glossary")?)
+        };
+
+        Ok(Self { terms, regex })
+    }
+
+    /// Replaces the terms found in `text`, matched case-sensitively, with placeholders standing
+    /// for their translations.
+    pub fn protect(&self, text: &str, placeholders: &mut Placeholders) -> String {
+        let regex = match &self.regex {
+            Some(regex) => regex,
+            None => return text.to_string(),
+        };
+
+        regex
+            .replace_all(text, |captures: &regex::Captures| {
+                let term = &captures[0];
+                placeholders.add(self.terms.get(term).map_or(term, String::as_str))
+            })
+            .into_owned()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Fields of a line separated by `separator`, which can be quoted with `"`, `""` standing for a
+/// quote inside the quotes.
+fn split_fields(line: &str, separator: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary(files: &[(&str, &str)]) -> Glossary {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+        Glossary::load(dir.path(), "en", "es").unwrap()
+    }
+
+    /// `text` protected by `glossary`, with the placeholders restored as `[translation]`.
+    fn protect(glossary: &Glossary, text: &str) -> String {
+        let mut placeholders = Placeholders::default();
+        let protected = glossary.protect(text, &mut placeholders);
+        regex::Regex::new(r"⟦(\d+)⟧")
+            .unwrap()
+            .replace_all(&protected, |captures: &regex::Captures| {
+                format!(
+                    "[{}]",
+                    placeholders.replacements()[captures[1].parse::<usize>().unwrap()]
+                )
+            })
+            .into_owned()
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(split_fields("a,b", ','), ["a", "b"]);
+        assert_eq!(
+            split_fields(r#""New York, NY",Nueva York"#, ','),
+            ["New York, NY", "Nueva York"]
+        );
+        assert_eq!(
+            split_fields(r#""say ""hi""","di ""hola""""#, ','),
+            [r#"say "hi""#, r#"di "hola""#]
+        );
+        assert_eq!(split_fields("a,b\tc", '\t'), ["a,b", "c"]);
+        assert_eq!(split_fields("alone", ','), ["alone"]);
+        assert_eq!(split_fields("a,", ','), ["a", ""]);
+    }
+
+    #[test]
+    fn load_files() {
+        let glossary = glossary(&[
+            (
+                "en-es.tsv",
+                "# Term\ttranslation\n\npull request\tsolicitud de cambios\nKubernetes\n",
+            ),
+            (
+                "en-es.csv",
+                "\"New York, NY\",\"Nueva York, NY\"\npull request,petición\n",
+            ),
+            ("all.csv", "Kubernetes,K8s\nRust,\n"),
+            ("en-fr.tsv", "pull request\tdemande de tirage\n"),
+        ]);
+
+        assert_eq!(
+            protect(
+                &glossary,
+                "Open a pull request on Kubernetes in Rust from New York, NY."
+            ),
+            "Open a [solicitud de cambios] on [Kubernetes] in [Rust] from [Nueva York, NY]."
+        );
+    }
+
+    #[test]
+    fn load_empty_term() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("all.tsv"), "term\tterme\n\ttranslation\n").unwrap();
+
+        let err = Glossary::load(dir.path(), "en", "fr").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}:2: The term is empty",
+                dir.path().join("all.tsv").display()
+            )
+        );
+    }
+
+    #[test]
+    fn load_missing_dir() {
+        let glossary = Glossary::load(Path::new("/nonexistent"), "en", "fr").unwrap();
+
+        assert_eq!(
+            protect(&glossary, "Nothing to protect"),
+            "Nothing to protect"
+        );
+    }
+
+    #[test]
+    fn longest_first() {
+        let glossary = glossary(&[(
+            "en-es.tsv",
+            "York\tYork\nNew York\tNueva York\nNew York City\tCiudad de Nueva York\n",
+        )]);
+
+        assert_eq!(
+            protect(&glossary, "New York City, New York and York"),
+            "[Ciudad de Nueva York], [Nueva York] and [York]"
+        );
+    }
+
+    #[test]
+    fn ascii_word_boundaries() {
+        let glossary = glossary(&[("en-es.tsv", "Go\tGo\nC++\tC++\n.NET\t.NET\ncafé\tcafé\n")]);
+
+        assert_eq!(
+            protect(&glossary, "Google Go, Gopher, 用Go写, C++11 and ASP.NET"),
+            "Google [Go], Gopher, 用[Go]写, [C++]11 and ASP[.NET]"
+        );
+        // Matched case-sensitively, and inside words when not made of ASCII letters at its ends.
+        assert_eq!(protect(&glossary, "go cafés"), "go [café]s");
+    }
+}
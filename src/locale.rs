@@ -0,0 +1,493 @@
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::placeholder::Protection;
+use crate::translator::Translator;
+
+/// Fills the untranslated entries of the gettext PO file `po` with the translations of their
+/// `msgid` and `msgid_plural`, and marks them as fuzzy for a translator to review.
+///
+/// The other entries, the header and the obsolete entries are copied as they are.
+pub async fn fill_po(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    po: &str,
+    protection: &Protection,
+) -> anyhow::Result<String> {
+    let nplurals = regex::Regex::new(r"Plural-Forms:\s*nplurals\s*=\s*(\d+)")
+        .expect("The Plural-Forms regex needs to be valid.")
+        .captures(po)
+        .and_then(|captures| captures[1].parse::<usize>().ok());
+
+    let mut filled = String::new();
+    let mut entry = Vec::new();
+    for line in po.split_inclusive('\n').chain([""]) {
+        if !line.trim().is_empty() {
+            entry.push(line);
+            continue;
+        }
+
+        match PoEntry::parse(&entry) {
+            Some(parsed) if parsed.is_untranslated() => {
+                let translated = parsed
+                    .translate(translator, from, to, protection, nplurals)
+                    .await
+                    .with_context(|| format!("Failed to translate `{}`", parsed.msgid))?;
+                filled.push_str(&translated);
+            }
+            _ => filled.extend(entry.drain(..)),
+        }
+        entry.clear();
+        filled.push_str(line);
+    }
+
+    Ok(filled)
+}
+
+#[derive(Debug, Clone, Default)]
+struct PoEntry<'a> {
+    comments: Vec<&'a str>,
+    /// Lines of `msgctxt`, `msgid` and `msgid_plural`, copied as they are.
+    keys: Vec<&'a str>,
+    msgid: String,
+    msgid_plural: Option<String>,
+    /// `msgstr` strings, with their index for the plural forms.
+    msgstr: Vec<(Option<usize>, String)>,
+}
+
+impl<'a> PoEntry<'a> {
+    /// Parses the lines of an entry, `None` if they are not understood.
+    fn parse(lines: &[&'a str]) -> Option<Self> {
+        let mut entry = Self::default();
+        // Keyword of the string continued by the lines starting with a quote.
+        let mut keyword = None;
+        for line in lines {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                entry.comments.push(line);
+                continue;
+            }
+            let string = match trimmed.split_once(char::is_whitespace) {
+                _ if trimmed.starts_with('"') => trimmed,
+                Some((line_keyword, string)) => {
+                    keyword = Some(line_keyword);
+                    string.trim()
+                }
+                None => return None,
+            };
+            let string = unescape(string.strip_prefix('"')?.strip_suffix('"')?);
+            let is_continuation = trimmed.starts_with('"');
+
+            match keyword? {
+                "msgctxt" => {}
+                "msgid" if is_continuation => entry.msgid.push_str(&string),
+                "msgid" => entry.msgid = string,
+                "msgid_plural" if is_continuation => entry.msgid_plural.as_mut()?.push_str(&string),
+                "msgid_plural" => entry.msgid_plural = Some(string),
+                _ if is_continuation => entry.msgstr.last_mut()?.1.push_str(&string),
+                "msgstr" => entry.msgstr.push((None, string)),
+                msgstr => {
+                    let index = msgstr.strip_prefix("msgstr[")?.strip_suffix(']')?;
+                    entry.msgstr.push((Some(index.parse().ok()?), string));
+                }
+            }
+            if entry.msgstr.is_empty() {
+                entry.keys.push(line);
+            }
+        }
+
+        Some(entry)
+    }
+
+    /// Whether the entry has a `msgid` but no translation, the header has an empty `msgid`.
+    fn is_untranslated(&self) -> bool {
+        !self.msgid.is_empty() && self.msgstr.iter().all(|(_, string)| string.is_empty())
+    }
+
+    /// The entry translated and marked as fuzzy, with `nplurals` plural forms if it has a plural.
+    async fn translate(
+        &self,
+        translator: &impl Translator,
+        from: &str,
+        to: &str,
+        protection: &Protection,
+        nplurals: Option<usize>,
+    ) -> anyhow::Result<String> {
+        let mut translated = String::new();
+        let flags = self.comments.iter().position(|line| line.starts_with("#,"));
+        // The flags go before the previous strings, `#|`, if there are no flags yet.
+        let fuzzy_at = flags.unwrap_or_else(|| {
+            self.comments
+                .iter()
+                .position(|line| line.starts_with("#|"))
+                .unwrap_or(self.comments.len())
+        });
+        for (index, line) in self.comments.iter().enumerate() {
+            if index == fuzzy_at && flags.is_none() {
+                translated.push_str("#, fuzzy\n");
+            }
+            if Some(index) == flags && !line.contains("fuzzy") {
+                translated.push_str(&format!("#, fuzzy,{}", &line[2..]));
+            } else {
+                translated.push_str(line);
+            }
+        }
+        if fuzzy_at == self.comments.len() {
+            translated.push_str("#, fuzzy\n");
+        }
+        translated.extend(self.keys.iter().copied());
+
+        let msgid = translate_text(translator, from, to, protection, &self.msgid).await?;
+        let msgid_plural = match &self.msgid_plural {
+            Some(msgid_plural) => {
+                translate_text(translator, from, to, protection, msgid_plural).await?
+            }
+            None => {
+                translated.push_str(&po_string("msgstr", &msgid));
+                return Ok(translated);
+            }
+        };
+
+        let mut indices: Vec<usize> = self.msgstr.iter().filter_map(|(index, _)| *index).collect();
+        indices.extend(0..nplurals.unwrap_or(2));
+        indices.sort_unstable();
+        indices.dedup();
+        for index in indices {
+            // Languages with a single form use it for every number, like the plural.
+            let string = if index == 0 && nplurals != Some(1) {
+                &msgid
+            } else {
+                &msgid_plural
+            };
+            translated.push_str(&po_string(&format!("msgstr[{}]", index), string));
+        }
+
+        Ok(translated)
+    }
+}
+
+/// `keyword` and `string` as a PO line, split after the newlines like gettext does.
+fn po_string(keyword: &str, string: &str) -> String {
+    let lines: Vec<&str> = string.split_inclusive('\n').collect();
+    if lines.len() <= 1 {
+        return format!("{} \"{}\"\n", keyword, escape(string));
+    }
+
+    let mut po_string = format!("{} \"\"\n", keyword);
+    for line in lines {
+        po_string.push_str(&format!("\"{}\"\n", escape(line)));
+    }
+    po_string
+}
+
+fn escape(string: &str) -> String {
+    string
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+fn unescape(string: &str) -> String {
+    let mut unescaped = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Translates the strings of the JSON i18n file `source` which are missing or empty in `target`,
+/// keeping the translations already in `target`. Nested objects are followed, the keys come in the
+/// order of `source` then those only in `target`.
+pub async fn fill_json(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    source: &str,
+    target: Option<&str>,
+    protection: &Protection,
+) -> anyhow::Result<String> {
+    let source: Value = serde_json::from_str(source).context("Failed to parse the source file")?;
+    let target: Option<Value> = target
+        .filter(|target| !target.trim().is_empty())
+        .map(serde_json::from_str)
+        .transpose()
+        .context("Failed to parse the translated file")?;
+    if !source.is_object() || target.as_ref().is_some_and(|target| !target.is_object()) {
+        return Err(anyhow::anyhow!("The JSON files need to hold objects"));
+    }
+
+    let mut texts = Vec::new();
+    missing_strings(&source, target.as_ref(), &mut texts);
+    let mut translations = Vec::with_capacity(texts.len());
+    for text in texts {
+        let translated = translate_text(translator, from, to, protection, text)
+            .await
+            .with_context(|| format!("Failed to translate `{}`", text))?;
+        translations.push(translated);
+    }
+
+    let filled = merge(&source, target.as_ref(), &mut translations.into_iter());
+    Ok(serde_json::to_string_pretty(&filled)? + "\n")
+}
+
+/// Whether a string of the source is missing from the translated file.
+fn is_missing(target: Option<&Value>) -> bool {
+    match target {
+        None | Some(Value::Null) => true,
+        Some(Value::String(string)) => string.is_empty(),
+        Some(_) => false,
+    }
+}
+
+fn missing_strings<'a>(source: &'a Value, target: Option<&Value>, texts: &mut Vec<&'a str>) {
+    match source {
+        Value::Object(source) => {
+            for (key, value) in source {
+                missing_strings(value, target.and_then(|target| target.get(key)), texts);
+            }
+        }
+        Value::String(text) if is_missing(target) && !text.is_empty() => texts.push(text),
+        _ => {}
+    }
+}
+
+/// `target` completed with the values of `source`, and `translations` for the strings, in the
+/// order of [`missing_strings`].
+fn merge(
+    source: &Value,
+    target: Option<&Value>,
+    translations: &mut impl Iterator<Item = String>,
+) -> Value {
+    match source {
+        Value::Object(source) => {
+            let mut merged = serde_json::Map::new();
+            for (key, value) in source {
+                let target = target.and_then(|target| target.get(key));
+                merged.insert(key.clone(), merge(value, target, translations));
+            }
+            if let Some(Value::Object(target)) = target {
+                for (key, value) in target {
+                    if !merged.contains_key(key) {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(merged)
+        }
+        Value::String(text) if is_missing(target) && !text.is_empty() => {
+            Value::String(translations.next().unwrap_or_else(|| text.clone()))
+        }
+        _ => target.unwrap_or(source).clone(),
+    }
+}
+
+/// Translates `text`, keeping the whitespace around it which the translators drop.
+async fn translate_text(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    protection: &Protection,
+    text: &str,
+) -> anyhow::Result<String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Ok(text.to_string());
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + trimmed.len();
+    let translated = protection.translate(translator, from, to, trimmed).await?;
+
+    Ok(format!(
+        "{}{}{}",
+        &text[..start],
+        translated.trim(),
+        &text[end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Translates to upper case, and keeps the texts it was asked to translate.
+    #[derive(Default)]
+    struct Upper {
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl Translator for Upper {
+        async fn translate(&self, _from: &str, _to: &str, text: &str) -> anyhow::Result<String> {
+            self.requests.lock().unwrap().push(text.to_string());
+            Ok(text.to_uppercase())
+        }
+    }
+
+    fn protection() -> Protection {
+        Protection {
+            shield: true,
+            ..Default::default()
+        }
+    }
+
+    async fn po(po: &str) -> String {
+        fill_po(&Upper::default(), "en", "fr", po, &protection())
+            .await
+            .unwrap()
+    }
+
+    const HEADER: &str = "msgid \"\"\nmsgstr \"\"\n\"Language: fr\\n\"\n\
+                          \"Plural-Forms: nplurals=2; plural=(n > 1);\\n\"\n";
+
+    #[tokio::test]
+    async fn po_fuzzy_flags() {
+        let filled = po(&format!(
+            "{}\n\
+             #: src/a.c:1\n#, c-format\nmsgid \"Hello %s\"\nmsgstr \"\"\n\n\
+             # Translator comment\n#| msgid \"Helo\"\nmsgid \"Bye\"\nmsgstr \"\"\n\n\
+             msgid \"Yes\"\nmsgstr \"\"\n\n\
+             #, fuzzy, c-format\nmsgid \"No %d\"\nmsgstr \"\"\n",
+            HEADER
+        ))
+        .await;
+
+        assert_eq!(
+            filled,
+            format!(
+                "{}\n\
+                 #: src/a.c:1\n#, fuzzy, c-format\nmsgid \"Hello %s\"\nmsgstr \"HELLO %s\"\n\n\
+                 # Translator comment\n#, fuzzy\n#| msgid \"Helo\"\nmsgid \"Bye\"\nmsgstr \"BYE\"\n\n\
+                 #, fuzzy\nmsgid \"Yes\"\nmsgstr \"YES\"\n\n\
+                 #, fuzzy, c-format\nmsgid \"No %d\"\nmsgstr \"NO %d\"\n",
+                HEADER
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn po_kept_entries() {
+        let po_file = format!(
+            "# Header comment\n{}\n\
+             msgctxt \"menu\"\nmsgid \"Open\"\nmsgstr \"Ouvrir\"\n\n\
+             #~ msgid \"Old\"\n#~ msgstr \"\"\n",
+            HEADER
+        );
+
+        assert_eq!(po(&po_file).await, po_file);
+    }
+
+    #[tokio::test]
+    async fn po_multi_line_strings() {
+        let filled = po(
+            "msgctxt \"help\"\nmsgid \"\"\n\"First \\\"line\\\"\\n\"\n\"second\\tline\"\nmsgstr \"\"\n\"\"\n",
+        )
+        .await;
+
+        assert_eq!(
+            filled,
+            "#, fuzzy\nmsgctxt \"help\"\nmsgid \"\"\n\"First \\\"line\\\"\\n\"\n\"second\\tline\"\n\
+             msgstr \"\"\n\"FIRST \\\"LINE\\\"\\n\"\n\"SECOND\\tLINE\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn po_plural_forms() {
+        let entry =
+            "msgid \"%d file\"\nmsgid_plural \"%d files\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n";
+        let plural = |nplurals: &str| {
+            format!(
+                "msgid \"\"\nmsgstr \"\"\n\"Plural-Forms: nplurals={}; plural=0;\\n\"\n\n{}",
+                nplurals, entry
+            )
+        };
+        let filled = |po: &str| po.split("\n\n").nth(1).unwrap().to_string();
+
+        assert_eq!(
+            filled(&po(&plural("3")).await),
+            "#, fuzzy\nmsgid \"%d file\"\nmsgid_plural \"%d files\"\n\
+             msgstr[0] \"%d FILE\"\nmsgstr[1] \"%d FILES\"\nmsgstr[2] \"%d FILES\"\n"
+        );
+        assert_eq!(
+            filled(&po(&plural("1")).await),
+            "#, fuzzy\nmsgid \"%d file\"\nmsgid_plural \"%d files\"\n\
+             msgstr[0] \"%d FILES\"\nmsgstr[1] \"%d FILES\"\n"
+        );
+        // Two forms without a Plural-Forms header.
+        assert_eq!(
+            po("msgid \"%d file\"\nmsgid_plural \"%d files\"\nmsgstr[0] \"\"\n").await,
+            "#, fuzzy\nmsgid \"%d file\"\nmsgid_plural \"%d files\"\n\
+             msgstr[0] \"%d FILE\"\nmsgstr[1] \"%d FILES\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_merge_order() {
+        let translator = Upper::default();
+
+        let filled = fill_json(
+            &translator,
+            "en",
+            "fr",
+            r#"{"title": "Bye", "menu": {"open": " Open {name} ", "empty": "", "close": "Close"}, "count": 3}"#,
+            Some(r#"{"extra": "Extra", "menu": {"close": "Fermer", "open": null}, "title": "Au revoir"}"#),
+            &protection(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            filled,
+            "{\n  \"title\": \"Au revoir\",\n  \"menu\": {\n    \"open\": \" OPEN {name} \",\n    \
+             \"empty\": \"\",\n    \"close\": \"Fermer\"\n  },\n  \"count\": 3,\n  \"extra\": \"Extra\"\n}\n"
+        );
+        assert_eq!(translator.requests.into_inner().unwrap(), ["Open ⟦0⟧"]);
+    }
+
+    #[tokio::test]
+    async fn json_without_target() {
+        let filled = fill_json(
+            &Upper::default(),
+            "en",
+            "fr",
+            r#"{"b": "Two", "a": "One"}"#,
+            Some(" \n"),
+            &protection(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(filled, "{\n  \"b\": \"TWO\",\n  \"a\": \"ONE\"\n}\n");
+    }
+
+    #[tokio::test]
+    async fn json_needs_objects() {
+        let err = fill_json(
+            &Upper::default(),
+            "en",
+            "fr",
+            "[\"One\"]",
+            None,
+            &protection(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "The JSON files need to hold objects");
+    }
+}
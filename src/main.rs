@@ -1,16 +1,55 @@
+mod batch;
+mod capture;
+mod cleanup;
+mod config;
+mod daemon;
+mod dbus;
+mod dictionary;
 mod env_info;
+mod glossary;
+mod locale;
+mod margins;
+mod markdown;
+mod ocr;
+mod output;
+mod placeholder;
+mod placement;
+mod size;
+mod source;
+mod subtitles;
 mod translator;
+mod watch;
 mod window;
 
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use anyhow::Context;
+use batch::{BatchFormat, Record};
+use clap::{CommandFactory, Parser};
+use cleanup::CleanupStep;
+use config::{config_dir, data_dir, Config, LlmConfig, Shortcuts};
 use gtk4::{prelude::*, Application};
-use window::TranslationWindowConfig;
+use output::{JsonTranslation, OutputFormat};
+use placeholder::Protection;
+use placement::Placement;
+use size::{Dimensions, WindowSize};
+use source::Source;
+use translator::{
+    AnyTranslator, Backend, CommandTranslator, GoogleTranslator, LlmTranslator, OfflineTranslator,
+};
+use window::{
+    tokio_runtime, translate_text, translate_text_detailed, CopyTarget, TranslationWindowConfig,
+};
+
+const APP_ID: &str = "com.github.plzfgme.wl_translation_window";
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub subcommand: Option<Command>,
     #[arg(
         short,
         long,
@@ -29,37 +68,663 @@ pub struct Args {
         help = "Text to translate, if not provided, stdin will be used"
     )]
     pub src_text: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Where to read the text to translate from [default: arg if --src-text is given, stdin otherwise]"
+    )]
+    pub source: Option<Source>,
+    #[arg(
+        long,
+        help = "Translate the text recognized in this PNG/JPEG/... image, - to read it from stdin"
+    )]
+    pub image: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with = "image",
+        help = "Drag a rectangle on the screen and translate the text recognized in it, Escape or right click cancels"
+    )]
+    pub capture_region: bool,
+    #[arg(
+        long,
+        help = "Tesseract language(s) of the text in --image, e.g. eng+jpn [default: derived from --from-lang]"
+    )]
+    pub ocr_lang: Option<String>,
+    #[arg(skip)]
+    pub ocr_confidence: Option<i32>,
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Clean up the text before translating it, steps separated by commas"
+    )]
+    pub cleanup: Vec<CleanupStep>,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        help = "Where to put the window [default: under-cursor]"
+    )]
+    pub placement: Option<Placement>,
+    #[arg(
+        long,
+        help = "Gap in pixels between the pointer and the window placed under it [default: 0]"
+    )]
+    pub cursor_offset: Option<i32>,
+    #[arg(
+        long,
+        help = "Window size as WIDTHxHEIGHT in pixels or percent of the screen (e.g. 600x25%), or auto to fit the text [default: 25%x25%]"
+    )]
+    pub size: Option<WindowSize>,
+    #[arg(long, help = "Minimum window size as WIDTHxHEIGHT [default: 0x0]")]
+    pub min_size: Option<Dimensions>,
+    #[arg(
+        long,
+        help = "Maximum window size as WIDTHxHEIGHT [default: 50%x50% with --size auto, 100%x100% otherwise]"
+    )]
+    pub max_size: Option<Dimensions>,
+    #[arg(long, help = "Close the window when it loses keyboard focus")]
+    pub close_on_focus_loss: bool,
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Close the window when the pointer has left it for this many milliseconds"
+    )]
+    pub close_on_leave: Option<u64>,
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Close the window after this many milliseconds"
+    )]
+    pub close_after: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "clipboard",
+        help = "Copy the translation as soon as it arrives, to the clipboard if no target is given"
+    )]
+    pub auto_copy: Option<CopyTarget>,
+    #[arg(
+        long,
+        help = "Keep running and open a window for every new selected text (primary selection), SIGUSR1 pauses and resumes"
+    )]
+    pub watch: bool,
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "How long the selection must stay the same before it is translated in --watch mode [default: 500]"
+    )]
+    pub watch_delay: Option<u64>,
+    #[arg(long, help = "Start --watch mode paused")]
+    pub watch_paused: bool,
+    #[arg(
+        long,
+        help = "Keep running and open the windows requested by the other invocations, which then return immediately, also serves the D-Bus interface"
+    )]
+    pub daemon: bool,
+    #[arg(
+        long,
+        help = "Open the window in this process even if a daemon is running"
+    )]
+    pub standalone: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "daemon"],
+        help = "Print the translation on stdout instead of opening a window"
+    )]
+    pub headless: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "Format of the translation printed by --headless, and of the batch command without its own --format [default: text]"
+    )]
+    pub format: Option<OutputFormat>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Translation service, with --daemon the one of the daemon is used [default: google]"
+    )]
+    pub backend: Option<Backend>,
+    #[arg(
+        long,
+        help = "Directory of the models of --backend offline, with one directory per language pair like en-zh [default: $XDG_DATA_HOME/wl_translation_window/models]"
+    )]
+    pub offline_model_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Base URL of the OpenAI-compatible API of --backend llm, e.g. http://localhost:11434/v1 [default: https://api.openai.com/v1]"
+    )]
+    pub llm_url: Option<String>,
+    #[arg(long, help = "Model of --backend llm")]
+    pub llm_model: Option<String>,
+    #[arg(
+        long,
+        help = "Extra instructions for --backend llm, like the subject of the text or the tone of the translation"
+    )]
+    pub llm_context: Option<String>,
+    #[arg(
+        long,
+        help = "Program of --backend command, with {from} and {to} replaced by the languages, reading the text on stdin and writing the translation on stdout, e.g. \"trans -b {from}:{to}\""
+    )]
+    pub command: Option<String>,
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "How long the program of --backend command may run [default: 10000]"
+    )]
+    pub command_timeout: Option<u64>,
+    #[arg(
+        long = "dictionary-dir",
+        value_name = "DIR",
+        help = "Directory of StarDict or dictd dictionaries to look single words up in, can be repeated [default: the usual StarDict and dictd directories]"
+    )]
+    pub dictionary_dirs: Vec<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory of the glossaries, {from}-{to}.tsv or .csv files of terms and their translations, and all.tsv or all.csv for every language pair [default: $XDG_CONFIG_HOME/wl_translation_window/glossary]"
+    )]
+    pub glossary_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Send the code, URLs, paths, format placeholders like {name} or %s and the markup to the translator instead of keeping them as they are"
+    )]
+    pub no_shield: bool,
+    #[arg(
+        long,
+        help = "Read the text as Markdown and only translate its headings, paragraphs and list items, keeping the code, links and front matter"
+    )]
+    pub markdown: bool,
+    #[arg(
+        short,
+        long,
+        help = "Config file, defaults to $XDG_CONFIG_HOME/wl_translation_window/config.toml"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(skip)]
+    pub shortcuts: Shortcuts,
+    #[arg(skip)]
+    pub llm: LlmConfig,
+}
+
+/// Translations of files, run without a window.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Translate the cues of an SRT or WebVTT subtitle file, keeping their numbers and timings
+    Subtitles {
+        #[arg(help = "SRT or WebVTT file, - to read it from stdin")]
+        input: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the translated subtitles [default: stdout]"
+        )]
+        output: Option<PathBuf>,
+        #[arg(long, help = "How many cues to translate in one request [default: 20]")]
+        batch_size: Option<usize>,
+    },
+    /// Fill the untranslated entries of a gettext PO file or a JSON i18n file, marked as fuzzy in
+    /// the PO files
+    Locale {
+        #[arg(help = "PO or JSON file to fill, created for JSON if it does not exist")]
+        file: PathBuf,
+        #[arg(
+            long,
+            help = "JSON file in the language to translate from, required for JSON"
+        )]
+        source: Option<PathBuf>,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the filled file, required for a .pot template [default: FILE, updated in place]"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Translate the lines of stdin, or files, several at the same time, printing the
+    /// translations in their order
+    Batch {
+        #[arg(help = "Files to translate, each one as a whole, instead of the lines of stdin")]
+        files: Vec<PathBuf>,
+        #[arg(
+            short = '0',
+            long,
+            conflicts_with = "files",
+            help = "Separate the texts of stdin with NUL characters instead of newlines"
+        )]
+        null: bool,
+        #[arg(
+            short,
+            long,
+            help = "How many texts to translate at the same time [default: 4]"
+        )]
+        jobs: Option<usize>,
+        #[arg(long, value_enum, help = "Format of the translations [default: tsv]")]
+        format: Option<BatchFormat>,
+    },
+}
+
+impl Args {
+    /// Fills the options not given on the command line from `config`.
+    pub fn merge(&mut self, config: Config) {
+        self.source = self.source.or(config.source);
+        self.ocr_lang = self.ocr_lang.take().or(config.ocr_lang);
+        if self.cleanup.is_empty() {
+            self.cleanup = config.cleanup.unwrap_or_default();
+        }
+        self.placement = self.placement.or(config.placement);
+        self.cursor_offset = self.cursor_offset.or(config.cursor_offset);
+        self.size = self.size.or(config.size);
+        self.min_size = self.min_size.or(config.min_size);
+        self.max_size = self.max_size.or(config.max_size);
+        self.close_on_focus_loss |= config.close_on_focus_loss.unwrap_or_default();
+        self.close_on_leave = self.close_on_leave.or(config.close_on_leave);
+        self.close_after = self.close_after.or(config.close_after);
+        self.auto_copy = self.auto_copy.or(config.auto_copy);
+        self.watch_delay = self.watch_delay.or(config.watch_delay);
+        self.backend = self.backend.or(config.backend);
+        self.offline_model_dir = self.offline_model_dir.take().or(config.offline_model_dir);
+        self.command = self.command.take().or(config.command);
+        self.command_timeout = self.command_timeout.or(config.command_timeout);
+        if self.dictionary_dirs.is_empty() {
+            self.dictionary_dirs = config
+                .dictionary_dirs
+                .unwrap_or_else(dictionary::default_dirs);
+        }
+        self.glossary_dir = self
+            .glossary_dir
+            .take()
+            .or(config.glossary_dir)
+            .or_else(|| config_dir().map(|dir| dir.join("glossary")));
+        self.no_shield |= !config.shield.unwrap_or(true);
+        self.markdown |= config.markdown.unwrap_or_default();
+        self.shortcuts = config.shortcuts;
+        self.llm = LlmConfig {
+            url: self.llm_url.clone().or(config.llm.url),
+            model: self.llm_model.clone().or(config.llm.model),
+            context: self.llm_context.clone().or(config.llm.context),
+            ..config.llm
+        };
+    }
+
+    /// Translator of `--backend`.
+    pub fn translator(&self) -> anyhow::Result<AnyTranslator> {
+        match self.backend.unwrap_or_default() {
+            Backend::Google => Ok(AnyTranslator::Google(GoogleTranslator::new())),
+            Backend::Offline => {
+                let model_dir = self
+                    .offline_model_dir
+                    .clone()
+                    .or_else(|| data_dir().map(|dir| dir.join("models")))
+                    .ok_or_else(|| anyhow::anyhow!("--offline-model-dir is required"))?;
+                Ok(AnyTranslator::Offline(OfflineTranslator::new(model_dir)))
+            }
+            Backend::Llm => LlmTranslator::new(self.llm.clone()).map(AnyTranslator::Llm),
+            Backend::Command => {
+                let command = self.command.as_deref().ok_or_else(|| {
+                    anyhow::anyhow!("--command is required with --backend command")
+                })?;
+                let timeout = Duration::from_millis(self.command_timeout.unwrap_or(10000));
+                CommandTranslator::new(command, timeout).map(AnyTranslator::Command)
+            }
+        }
+    }
 }
 
 impl From<Args> for TranslationWindowConfig {
     fn from(args: Args) -> Self {
+        let size = args.size.unwrap_or_default();
+        let default_max_size = match size {
+            WindowSize::Auto => Dimensions::percent(50.0, 50.0),
+            WindowSize::Fixed(_) => Dimensions::percent(100.0, 100.0),
+        };
+
         TranslationWindowConfig {
             src_text: args.src_text.unwrap_or_default(),
+            ocr_confidence: args.ocr_confidence,
+            cleanup: args.cleanup,
+            dictionary_dirs: args.dictionary_dirs,
+            glossary_dir: args.glossary_dir,
+            shield: !args.no_shield,
+            markdown: args.markdown,
             from_lang: args.from_lang,
             to_lang: args.to_lang,
+            placement: args.placement.unwrap_or_default(),
+            cursor_offset: args.cursor_offset.unwrap_or_default(),
+            size,
+            min_size: args.min_size.unwrap_or(Dimensions::percent(0.0, 0.0)),
+            max_size: args.max_size.unwrap_or(default_max_size),
+            close_on_focus_loss: args.close_on_focus_loss,
+            close_on_leave: args.close_on_leave.map(Duration::from_millis),
+            close_after: args.close_after.map(Duration::from_millis),
+            shortcuts: args.shortcuts,
+            auto_copy: args.auto_copy,
         }
     }
 }
 
 fn main() {
     let mut args = Args::parse();
-    if args.src_text.is_none() {
-        let mut text = String::new();
-        std::io::stdin().read_to_string(&mut text).unwrap();
+    let uses_format = match args.subcommand {
+        Some(Command::Batch { .. }) => true,
+        Some(_) => false,
+        None => args.headless,
+    };
+    if args.format.is_some() && !uses_format {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--format needs --headless or the batch command",
+            )
+            .exit();
+    }
+    // The errors before the translation are printed as JSON too.
+    let json =
+        args.headless && args.subcommand.is_none() && args.format == Some(OutputFormat::Json);
+    let config = Config::load(args.config.as_deref())
+        .unwrap_or_else(|err| exit_with_error(err, json, args.src_text.as_deref(), args.backend));
+    args.merge(config);
+    // Only set when this process translates, a running daemon translates with its own.
+    let translator = args.translator();
+    let backend = args.backend.unwrap_or_default();
+    let format = args.format.unwrap_or_default();
+    if let Some(command) = args.subcommand.take() {
+        set_translator(translator, false, None, backend);
+        let config = TranslationWindowConfig::from(args);
+        if let Err(err) = run_command(command, &config, backend, format) {
+            eprintln!("Error: {:#}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let watch = args.watch;
+    let watch_delay = Duration::from_millis(args.watch_delay.unwrap_or(500));
+    let watch_paused = args.watch_paused;
+    let daemon = args.daemon;
+    let standalone = args.standalone;
+    let headless = args.headless;
+    if args.image.is_some() || args.capture_region {
+        let lang = args
+            .ocr_lang
+            .clone()
+            .unwrap_or_else(|| ocr::tesseract_lang(&args.from_lang).to_string());
+        let recognized = match args.image.take() {
+            Some(image) => ocr::recognize_file(&image, &lang).map(Some),
+            None => capture::capture_region().and_then(|frame| {
+                frame
+                    .map(|frame| ocr::recognize_frame(&frame, &lang))
+                    .transpose()
+            }),
+        };
+        let recognized = match recognized {
+            Ok(Some(recognized)) => recognized,
+            Ok(None) => return,
+            Err(err) => exit_with_error(err, json, None, Some(backend)),
+        };
+        args.src_text.replace(recognized.text);
+        args.ocr_confidence.replace(recognized.confidence);
+    } else if !watch && !daemon {
+        let source = args.source.unwrap_or(if args.src_text.is_some() {
+            Source::Arg
+        } else {
+            Source::Stdin
+        });
+        let src_text = args.src_text.take();
+        let text = source
+            .read(src_text.clone())
+            .unwrap_or_else(|err| exit_with_error(err, json, src_text.as_deref(), Some(backend)));
         args.src_text.replace(text);
     }
     let config = TranslationWindowConfig::from(args);
 
+    if !headless && !watch && !daemon && !standalone {
+        match daemon::send(&config) {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(err) => {
+                eprintln!("Error: {:#}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    set_translator(translator, json, Some(&config.src_text), backend);
+
+    if headless && format == OutputFormat::Json {
+        let start = Instant::now();
+        let translated = tokio_runtime().block_on(translate_text_detailed(
+            &config,
+            &config.from_lang,
+            &config.to_lang,
+            &config.src_text,
+        ));
+        let json = JsonTranslation::new(&config.src_text, &translated, backend, start.elapsed());
+        println!(
+            "{}",
+            serde_json::to_string(&json).expect("Translations need to be serializable.")
+        );
+        if translated.is_err() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if headless {
+        let translated = tokio_runtime().block_on(translate_text(
+            &config,
+            &config.from_lang,
+            &config.to_lang,
+            &config.src_text,
+        ));
+        match translated {
+            Ok(translated) => println!("{}", translated.trim_end_matches('\n')),
+            Err(err) => {
+                eprintln!("Error: {:#}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let application = Application::builder()
-        .application_id("com.github.plzfgme.wl_translation_window")
+        .application_id(APP_ID)
         .flags(gio::ApplicationFlags::NON_UNIQUE)
         .build();
 
     let window = window::TranslationWindow::new(&config);
 
     application.connect_activate(move |app| {
-        window.create(app);
+        if daemon {
+            if let Err(err) = daemon::start_daemon(app) {
+                eprintln!("Error: {:#}", err);
+            }
+            dbus::start_dbus_service(app, config.clone());
+        }
+        if watch {
+            watch::start_watching(app, config.clone(), watch_delay, watch_paused);
+        }
+        if !daemon && !watch {
+            window.create(app);
+        }
     });
 
     application.run_with_args(&Vec::<String>::new());
 }
+
+/// Sets the `translator` of this process, or exits with its error like [`exit_with_error`].
+fn set_translator(
+    translator: anyhow::Result<AnyTranslator>,
+    json: bool,
+    source: Option<&str>,
+    backend: Backend,
+) {
+    match translator {
+        // Nothing is translated before, so the translator is always set.
+        Ok(translator) => {
+            window::set_translator(translator);
+        }
+        Err(err) => exit_with_error(err, json, source, Some(backend)),
+    }
+}
+
+/// Prints `err`, which happened before the translation, and exits. With `json` it is printed
+/// like a failed translation of `source`.
+fn exit_with_error(
+    err: anyhow::Error,
+    json: bool,
+    source: Option<&str>,
+    backend: Option<Backend>,
+) -> ! {
+    if json {
+        let failed = Err(err);
+        let json = JsonTranslation::new(
+            source.unwrap_or_default(),
+            &failed,
+            backend.unwrap_or_default(),
+            Duration::ZERO,
+        );
+        println!(
+            "{}",
+            serde_json::to_string(&json).expect("Translations need to be serializable.")
+        );
+    } else {
+        eprintln!("Error: {:#}", err);
+    }
+    std::process::exit(1);
+}
+
+fn run_command(
+    command: Command,
+    config: &TranslationWindowConfig,
+    backend: Backend,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let protection = Protection::load(config, &config.from_lang, &config.to_lang)?;
+    match command {
+        Command::Subtitles {
+            input,
+            output,
+            batch_size,
+        } => {
+            let subtitles = read_input(&input)?;
+            let translated = tokio_runtime().block_on(subtitles::translate(
+                window::translator(),
+                &config.from_lang,
+                &config.to_lang,
+                &subtitles,
+                &protection,
+                batch_size.unwrap_or(20),
+            ))?;
+            write_output(output.as_deref(), &translated)
+        }
+        Command::Locale {
+            file,
+            source,
+            output,
+        } => {
+            let extension = file.extension().and_then(|extension| extension.to_str());
+            // A template is left for the other languages, not filled with one of them.
+            if extension == Some("pot") && output.is_none() {
+                return Err(anyhow::anyhow!(
+                    "--output is required to fill the template {}, e.g. fr.po",
+                    file.display()
+                ));
+            }
+            let filled = match extension {
+                Some("po" | "pot") => tokio_runtime().block_on(locale::fill_po(
+                    window::translator(),
+                    &config.from_lang,
+                    &config.to_lang,
+                    &read_input(&file)?,
+                    &protection,
+                ))?,
+                Some("json") => {
+                    let source = source.ok_or_else(|| {
+                        anyhow::anyhow!("--source is required to fill a JSON file")
+                    })?;
+                    let target = match std::fs::read_to_string(&file) {
+                        Ok(target) => Some(target),
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                        Err(err) => {
+                            return Err(err)
+                                .with_context(|| format!("Failed to read {}", file.display()))
+                        }
+                    };
+                    tokio_runtime().block_on(locale::fill_json(
+                        window::translator(),
+                        &config.from_lang,
+                        &config.to_lang,
+                        &read_input(&source)?,
+                        target.as_deref(),
+                        &protection,
+                    ))?
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Unsupported locale file {}, expected a .po or .json file",
+                        file.display()
+                    ))
+                }
+            };
+            write_output(Some(output.as_deref().unwrap_or(&file)), &filled)
+        }
+        Command::Batch {
+            files,
+            null,
+            jobs,
+            format: batch_format,
+        } => {
+            let records = if files.is_empty() {
+                batch::split_records(&read_input(Path::new("-"))?, null)
+            } else {
+                files
+                    .into_iter()
+                    .map(|file| {
+                        Ok(Record {
+                            text: read_input(&file)?,
+                            file: Some(file),
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            };
+            let batch_format = batch_format.unwrap_or(match format {
+                OutputFormat::Text => BatchFormat::Tsv,
+                OutputFormat::Json => BatchFormat::Jsonl,
+            });
+            let succeeded = tokio_runtime().block_on(batch::translate(
+                config,
+                backend,
+                &records,
+                jobs.unwrap_or(4),
+                batch_format,
+                &mut std::io::stdout().lock(),
+            ))?;
+            if !succeeded {
+                return Err(anyhow::anyhow!("Some of the texts failed to translate"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Contents of the file at `path`, or of stdin if `path` is `-`.
+fn read_input(path: &Path) -> anyhow::Result<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read stdin")?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+}
+
+/// Writes `contents` to the file at `path`, or to stdout if there is none.
+fn write_output(path: Option<&Path>, contents: &str) -> anyhow::Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => std::io::stdout()
+            .write_all(contents.as_bytes())
+            .context("Failed to write to stdout"),
+    }
+}
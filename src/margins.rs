@@ -0,0 +1,230 @@
+//! Pure geometry behind [`crate::placement::Placement`], everything here is in output coordinates.
+
+/// Layer shell margins, the window fills the output minus these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Margins {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Margins {
+    /// Margins putting a `width`x`height` window at (`x`, `y`) on a `output_width`x`output_height`
+    /// output.
+    ///
+    /// The window is shrunk to the output and moved back onto it if needed, so the margins are
+    /// never negative.
+    pub fn at(
+        output_width: i32,
+        output_height: i32,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Self {
+        let (left, right) = span_at(output_width, x, width);
+        let (top, bottom) = span_at(output_height, y, height);
+
+        Margins {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    /// Margins putting a `width`x`height` window next to the pointer at (`pointer_x`,
+    /// `pointer_y`), `offset` pixels away from it on both axes.
+    ///
+    /// The window goes to the bottom right of the pointer, flips to the other side on an axis
+    /// where it does not fit, and is pushed back onto the output where it fits on neither side.
+    pub fn under_cursor(
+        output_width: i32,
+        output_height: i32,
+        pointer_x: i32,
+        pointer_y: i32,
+        width: i32,
+        height: i32,
+        offset: i32,
+    ) -> Self {
+        let (left, right) = span_next_to(output_width, pointer_x, width, offset);
+        let (top, bottom) = span_next_to(output_height, pointer_y, height, offset);
+
+        Margins {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+}
+
+/// Margins before and after a window of `size` starting at `start` on an axis of `length`.
+fn span_at(length: i32, start: i32, size: i32) -> (i32, i32) {
+    let length = length.max(0);
+    let size = size.clamp(0, length);
+    let start = start.clamp(0, length - size);
+
+    (start, length - start - size)
+}
+
+/// Margins before and after a window of `size` next to `pointer` on an axis of `length`.
+fn span_next_to(length: i32, pointer: i32, size: i32, offset: i32) -> (i32, i32) {
+    let length = length.max(0);
+    let size = size.clamp(0, length);
+    let pointer = pointer.clamp(0, length);
+
+    let after = pointer.saturating_add(offset);
+    let before = pointer.saturating_sub(offset).saturating_sub(size);
+    let start = if after.saturating_add(size) <= length {
+        after
+    } else if before >= 0 {
+        before
+    } else {
+        // Fits on neither side, stay as close to the pointer as possible.
+        length - size
+    };
+
+    span_at(length, start, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn under_cursor_at_the_origin() {
+        assert_eq!(
+            Margins::under_cursor(1920, 1080, 0, 0, 400, 300, 10),
+            Margins {
+                top: 10,
+                right: 1510,
+                bottom: 770,
+                left: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn under_cursor_flips_at_the_right_and_bottom_edges() {
+        assert_eq!(
+            Margins::under_cursor(1920, 1080, 1920, 1080, 400, 300, 10),
+            Margins {
+                top: 770,
+                right: 10,
+                bottom: 10,
+                left: 1510,
+            }
+        );
+    }
+
+    #[test]
+    fn under_cursor_pushed_back_when_fitting_on_neither_side() {
+        assert_eq!(
+            Margins::under_cursor(1000, 600, 500, 300, 800, 500, 10),
+            Margins {
+                top: 100,
+                right: 0,
+                bottom: 0,
+                left: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn under_cursor_with_a_negative_offset() {
+        assert_eq!(
+            Margins::under_cursor(1920, 1080, 100, 100, 400, 300, -10),
+            Margins {
+                top: 90,
+                right: 1430,
+                bottom: 690,
+                left: 90,
+            }
+        );
+    }
+
+    #[test]
+    fn window_larger_than_the_output() {
+        let zero = Margins {
+            top: 0,
+            right: 0,
+            bottom: 0,
+            left: 0,
+        };
+        assert_eq!(Margins::at(800, 600, 100, 100, 1000, 1000), zero);
+        assert_eq!(
+            Margins::under_cursor(800, 600, 400, 300, 1000, 1000, 10),
+            zero
+        );
+    }
+
+    #[test]
+    fn at_moves_the_window_back_onto_the_output() {
+        assert_eq!(
+            Margins::at(1920, 1080, 1800, 1000, 400, 300),
+            Margins {
+                top: 780,
+                right: 0,
+                bottom: 0,
+                left: 1520,
+            }
+        );
+        assert_eq!(
+            Margins::at(1920, 1080, -50, -50, 400, 300),
+            Margins {
+                top: 0,
+                right: 1520,
+                bottom: 780,
+                left: 0,
+            }
+        );
+    }
+
+    fn assert_fits(margins: Margins, output_width: i32, output_height: i32) {
+        assert!(margins.top >= 0 && margins.right >= 0);
+        assert!(margins.bottom >= 0 && margins.left >= 0);
+        assert!(margins.left + margins.right <= output_width);
+        assert!(margins.top + margins.bottom <= output_height);
+    }
+
+    proptest! {
+        #[test]
+        fn at_fits_on_the_output(
+            output_width in 0..=100_000,
+            output_height in 0..=100_000,
+            x in any::<i32>(),
+            y in any::<i32>(),
+            width in any::<i32>(),
+            height in any::<i32>(),
+        ) {
+            let margins = Margins::at(output_width, output_height, x, y, width, height);
+            assert_fits(margins, output_width, output_height);
+        }
+
+        #[test]
+        fn under_cursor_fits_on_the_output(
+            output_width in 0..=100_000,
+            output_height in 0..=100_000,
+            pointer_x in any::<i32>(),
+            pointer_y in any::<i32>(),
+            width in any::<i32>(),
+            height in any::<i32>(),
+            offset in any::<i32>(),
+        ) {
+            let margins = Margins::under_cursor(
+                output_width,
+                output_height,
+                pointer_x,
+                pointer_y,
+                width,
+                height,
+                offset,
+            );
+            assert_fits(margins, output_width, output_height);
+        }
+    }
+}
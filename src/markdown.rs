@@ -0,0 +1,386 @@
+use std::sync::LazyLock;
+
+use crate::placeholder::Protection;
+use crate::translator::Translator;
+
+static QUOTE: LazyLock<regex::Regex> = LazyLock::new(|| regex(r"^( {0,3}>[ \t]?)"));
+/// Thematic breaks and setext heading underlines.
+static BREAK: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex(r"^ {0,3}(?:(?:[-*_][ \t]*){3,}|=+[ \t]*|-+[ \t]*)$"));
+static HEADING: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex(r"^( {0,3}#{1,6}[ \t]+)(.*?)((?:[ \t]+#+)?[ \t]*)$"));
+static TABLE_DELIMITER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex(r"^ {0,3}\|?[ \t]*:?-+:?[ \t]*(?:\|[ \t]*:?-+:?[ \t]*)*\|?[ \t]*$"));
+static HTML: LazyLock<regex::Regex> = LazyLock::new(|| regex(r"^ {0,3}<[A-Za-z/!?]"));
+static LINK_REFERENCE: LazyLock<regex::Regex> = LazyLock::new(|| regex(r"^ {0,3}\[[^\]]+\]:[ \t]"));
+static INDENTED_CODE: LazyLock<regex::Regex> = LazyLock::new(|| regex(r"^(?: {4}|\t)"));
+/// Fences of code blocks, also indented in list items.
+static FENCE: LazyLock<regex::Regex> = LazyLock::new(|| regex(r"^[ \t]*(`{3,}|~{3,})"));
+/// List marker with its indentation and task checkbox.
+static LIST_MARKER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex(r"^[ \t]*(?:[-*+]|\d{1,9}[.)])[ \t]+(?:\[[ xX]\][ \t]+)?"));
+/// Lines starting a block which end the paragraph before them.
+static INTERRUPTION: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex(r"^ {0,3}(?:#{1,6}(?:[ \t]|$)|>|<[A-Za-z/!?]|=+[ \t]*$|-+[ \t]*$|(?:[-*_][ \t]*){3,}$)")
+});
+
+/// Part of a Markdown document.
+#[derive(Debug, Clone)]
+enum Block {
+    /// Lines copied as they are, like code blocks, tables or the front matter.
+    Kept(String),
+    /// Prose of a heading, paragraph or list item, on a single line after `prefix`.
+    Prose {
+        prefix: String,
+        text: String,
+        suffix: String,
+    },
+    /// Blocks of a blockquote, each line of which gets `prefix`.
+    Quote { prefix: String, blocks: Vec<Block> },
+}
+
+/// Translates the headings, paragraphs and list items of the Markdown `document` one by one,
+/// keeping its front matter, code, tables, HTML blocks and link targets as they are.
+///
+/// The lines of the paragraphs are joined, the rest of the document is unchanged.
+pub async fn translate(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    document: &str,
+    protection: &Protection,
+) -> anyhow::Result<String> {
+    let blocks = parse(document);
+    let mut texts = Vec::new();
+    collect_prose(&blocks, &mut texts);
+
+    let mut translations = Vec::with_capacity(texts.len());
+    for text in texts {
+        if text.trim().is_empty() {
+            translations.push(text);
+            continue;
+        }
+        let translated = protection.translate(translator, from, to, &text).await?;
+        translations.push(translated.trim().to_string());
+    }
+
+    let mut translated = String::new();
+    render(&blocks, &mut translations.into_iter(), &mut translated);
+    Ok(translated)
+}
+
+fn parse(document: &str) -> Vec<Block> {
+    let lines: Vec<&str> = document.split_inclusive('\n').collect();
+    let mut blocks = Vec::new();
+    let mut index = 0;
+
+    if lines.first().is_some_and(|line| line.trim_end() == "---") {
+        if let Some(end) = lines[1..]
+            .iter()
+            .position(|line| matches!(line.trim_end(), "---" | "..."))
+        {
+            blocks.push(Block::Kept(lines[..end + 2].concat()));
+            index = end + 2;
+        }
+    }
+
+    let mut in_list = false;
+    while index < lines.len() {
+        let line = lines[index];
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        if content.trim().is_empty() {
+            blocks.push(Block::Kept(line.to_string()));
+            index += 1;
+            continue;
+        }
+        // Lines of the list items are indented, except for the list markers.
+        in_list &= content.starts_with([' ', '\t']) || list_marker(content).is_some();
+
+        if let Some(end) = fenced_code_end(&lines, index) {
+            blocks.push(Block::Kept(lines[index..end].concat()));
+            index = end;
+            continue;
+        }
+
+        if let Some(captures) = QUOTE.captures(content) {
+            let prefix = captures[1].to_string();
+            let mut quoted = String::new();
+            while index < lines.len() {
+                let line = lines[index].trim_end_matches(['\n', '\r']);
+                let rest = match QUOTE.find(line) {
+                    Some(marker) => &line[marker.end()..],
+                    None => break,
+                };
+                quoted.push_str(rest);
+                quoted.push('\n');
+                index += 1;
+            }
+            blocks.push(Block::Quote {
+                prefix,
+                blocks: parse(&quoted),
+            });
+            continue;
+        }
+
+        if BREAK.is_match(content) || LINK_REFERENCE.is_match(content) {
+            blocks.push(Block::Kept(line.to_string()));
+            index += 1;
+            continue;
+        }
+
+        if let Some(captures) = HEADING.captures(content) {
+            blocks.push(Block::Prose {
+                prefix: captures[1].to_string(),
+                text: captures[2].to_string(),
+                suffix: captures[3].to_string(),
+            });
+            index += 1;
+            continue;
+        }
+
+        // HTML blocks, tables and indented code, up to the next empty line.
+        let is_table = content.contains('|')
+            && lines
+                .get(index + 1)
+                .is_some_and(|next| TABLE_DELIMITER.is_match(next.trim_end()));
+        if is_table || HTML.is_match(content) || (!in_list && INDENTED_CODE.is_match(content)) {
+            let end = lines[index..]
+                .iter()
+                .position(|line| line.trim().is_empty())
+                .map_or(lines.len(), |end| index + end);
+            blocks.push(Block::Kept(lines[index..end].concat()));
+            index = end;
+            continue;
+        }
+
+        let (prefix, first) = match list_marker(content) {
+            Some(marker) => {
+                in_list = true;
+                content.split_at(marker)
+            }
+            None => content.split_at(content.len() - content.trim_start().len()),
+        };
+        let mut text = first.trim().to_string();
+        index += 1;
+        while index < lines.len() && is_continuation(&lines, index) {
+            text.push(' ');
+            text.push_str(lines[index].trim());
+            index += 1;
+        }
+        blocks.push(Block::Prose {
+            prefix: prefix.to_string(),
+            text,
+            suffix: String::new(),
+        });
+    }
+
+    blocks
+}
+
+/// End of the fenced code block starting at `lines[index]`, if any.
+fn fenced_code_end(lines: &[&str], index: usize) -> Option<usize> {
+    let captures = FENCE.captures(lines[index])?;
+    let fence = &captures[1];
+    let end = lines[index + 1..]
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            line.starts_with(fence) && line.trim_start_matches(&fence[..1]).is_empty()
+        })
+        .map_or(lines.len(), |end| index + end + 2);
+
+    Some(end)
+}
+
+/// Length of the list marker of `line`.
+fn list_marker(line: &str) -> Option<usize> {
+    LIST_MARKER.find(line).map(|marker| marker.end())
+}
+
+/// Whether `lines[index]` continues the paragraph or list item of the previous line.
+fn is_continuation(lines: &[&str], index: usize) -> bool {
+    let line = lines[index].trim_end_matches(['\n', '\r']);
+
+    !line.trim().is_empty()
+        && list_marker(line).is_none()
+        && fenced_code_end(lines, index).is_none()
+        && !INTERRUPTION.is_match(line)
+}
+
+fn collect_prose(blocks: &[Block], texts: &mut Vec<String>) {
+    for block in blocks {
+        match block {
+            Block::Kept(_) => {}
+            Block::Prose { text, .. } => texts.push(text.clone()),
+            Block::Quote { blocks, .. } => collect_prose(blocks, texts),
+        }
+    }
+}
+
+fn render(blocks: &[Block], translations: &mut impl Iterator<Item = String>, output: &mut String) {
+    for block in blocks {
+        match block {
+            Block::Kept(lines) => output.push_str(lines),
+            Block::Prose {
+                prefix,
+                text,
+                suffix,
+            } => {
+                output.push_str(prefix);
+                output.push_str(&translations.next().unwrap_or_else(|| text.clone()));
+                output.push_str(suffix);
+                output.push('\n');
+            }
+            Block::Quote { prefix, blocks } => {
+                let mut quoted = String::new();
+                render(blocks, translations, &mut quoted);
+                for line in quoted.lines() {
+                    if line.is_empty() {
+                        output.push_str(prefix.trim_end());
+                    } else {
+                        output.push_str(prefix);
+                        output.push_str(line);
+                    }
+                    output.push('\n');
+                }
+            }
+        }
+    }
+}
+
+fn regex(pattern: &str) -> regex::Regex {
+    regex::Regex::new(pattern).expect("Markdown regexes need to be valid.")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Translates to upper case, and keeps the texts it was asked to translate.
+    #[derive(Default)]
+    struct Upper {
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl Translator for Upper {
+        async fn translate(&self, _from: &str, _to: &str, text: &str) -> anyhow::Result<String> {
+            self.requests.lock().unwrap().push(text.to_string());
+            Ok(text.to_uppercase())
+        }
+    }
+
+    /// `document` translated to upper case, and the texts sent to the translator.
+    async fn upper(document: &str) -> (String, Vec<String>) {
+        let translator = Upper::default();
+        let protection = Protection {
+            shield: true,
+            ..Default::default()
+        };
+        let translated = translate(&translator, "en", "fr", document, &protection)
+            .await
+            .unwrap();
+        (translated, translator.requests.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn front_matter_and_headings() {
+        let (translated, requests) = upper(
+            "---\ntitle: Hello\ntags: [a, b]\n---\n# Hello *world* #\n\nSetext title\n============\n\nSub\n---\n",
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "---\ntitle: Hello\ntags: [a, b]\n---\n# HELLO *WORLD* #\n\nSETEXT TITLE\n============\n\nSUB\n---\n"
+        );
+        assert_eq!(requests, ["Hello *world*", "Setext title", "Sub"]);
+    }
+
+    #[tokio::test]
+    async fn paragraphs_are_joined() {
+        let (translated, requests) = upper(
+            "A paragraph\non two lines with `code` and [a link](https://example.com/Path).\n***\nLast one\n",
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "A PARAGRAPH ON TWO LINES WITH `code` AND [A LINK](https://example.com/Path).\n***\nLAST ONE\n"
+        );
+        assert_eq!(requests.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn lists_with_fences() {
+        let (translated, requests) = upper(
+            "- First item\n  continued\n- [x] Done\n\n  ```sh\n  echo keep me\n\n  - not an item\n  ```\n\n  Inside the item\n\n1. One\n2) Two\n",
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "- FIRST ITEM CONTINUED\n- [x] DONE\n\n  ```sh\n  echo keep me\n\n  - not an item\n  ```\n\n  INSIDE THE ITEM\n\n1. ONE\n2) TWO\n"
+        );
+        assert_eq!(
+            requests,
+            [
+                "First item continued",
+                "Done",
+                "Inside the item",
+                "One",
+                "Two"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn tables_code_and_html_are_kept() {
+        let document = "| Name | Value |\n|:-----|------:|\n| a | b |\n\n    indented code\n\n<div>\nraw html\n</div>\n\n~~~\ncode\n~~~\n\n[ref]: https://example.com\n";
+
+        let (translated, requests) = upper(document).await;
+
+        assert_eq!(translated, document);
+        assert!(requests.is_empty(), "{:?}", requests);
+    }
+
+    #[tokio::test]
+    async fn nested_quotes() {
+        let (translated, requests) =
+            upper("> Quoted\n> text\n>\n> > Nested\n> > - item\n>\n> # Title\n\nAfter\n").await;
+
+        assert_eq!(
+            translated,
+            "> QUOTED TEXT\n>\n> > NESTED\n> > - ITEM\n>\n> # TITLE\n\nAFTER\n"
+        );
+        assert_eq!(
+            requests,
+            ["Quoted text", "Nested", "item", "Title", "After"]
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_by_an_identity_translation() {
+        let document = "---\na: b\n---\n# Title\n\n> quote\n> > nested\n\n- item\n  ```\n  code\n  ```\n\n| a | b |\n|---|---|\n";
+
+        let translator = Upper::default();
+        let protection = Protection::default();
+        let blocks = parse(document);
+        let mut texts = Vec::new();
+        collect_prose(&blocks, &mut texts);
+        let mut rendered = String::new();
+        render(&blocks, &mut texts.into_iter(), &mut rendered);
+
+        assert_eq!(rendered, document);
+        // An empty document sends nothing.
+        assert_eq!(
+            translate(&translator, "en", "fr", "", &protection)
+                .await
+                .unwrap(),
+            ""
+        );
+        assert!(translator.requests.into_inner().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::capture::Frame;
+
+/// Text recognized in an image.
+#[derive(Debug, Clone)]
+pub struct Recognized {
+    pub text: String,
+    /// Mean confidence of the words, in percent.
+    pub confidence: i32,
+}
+
+/// Recognizes the text of the PNG/JPEG/... image at `path`, or on stdin if `path` is `-`.
+pub fn recognize_file(path: &Path, lang: &str) -> anyhow::Result<Recognized> {
+    let image = if path == Path::new("-") {
+        let mut image = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut image)
+            .context("Failed to read the image from stdin")?;
+        image
+    } else {
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+
+    recognize_image(&image, lang)
+}
+
+#[cfg(feature = "ocr")]
+pub fn recognize_image(image: &[u8], lang: &str) -> anyhow::Result<Recognized> {
+    let tesseract = tesseract::Tesseract::new(None, Some(lang))
+        .with_context(|| format!("Failed to load the Tesseract data for `{}`", lang))?
+        .set_image_from_mem(image)
+        .context("Failed to decode the image")?;
+
+    recognize(tesseract)
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn recognize_image(_image: &[u8], _lang: &str) -> anyhow::Result<Recognized> {
+    Err(anyhow::anyhow!(
+        "Built without OCR support, enable the `ocr` feature"
+    ))
+}
+
+/// Recognizes the text of a screen capture.
+#[cfg(feature = "ocr")]
+pub fn recognize_frame(frame: &Frame, lang: &str) -> anyhow::Result<Recognized> {
+    let tesseract = tesseract::Tesseract::new(None, Some(lang))
+        .with_context(|| format!("Failed to load the Tesseract data for `{}`", lang))?
+        .set_frame(&frame.data, frame.width, frame.height, 3, frame.width * 3)
+        .context("Failed to load the screen capture")?;
+
+    recognize(tesseract)
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn recognize_frame(_frame: &Frame, _lang: &str) -> anyhow::Result<Recognized> {
+    Err(anyhow::anyhow!(
+        "Built without OCR support, enable the `ocr` feature"
+    ))
+}
+
+#[cfg(feature = "ocr")]
+fn recognize(tesseract: tesseract::Tesseract) -> anyhow::Result<Recognized> {
+    let mut tesseract = tesseract
+        .recognize()
+        .context("Failed to recognize the text")?;
+    let text = tesseract
+        .get_text()
+        .context("Failed to get the recognized text")?;
+
+    Ok(Recognized {
+        text,
+        confidence: tesseract.mean_text_conf(),
+    })
+}
+
+/// Tesseract language for a Google Translate language code. Codes without a known equivalent are
+/// assumed to be Tesseract languages already, e.g. `eng+jpn`.
+pub fn tesseract_lang(lang: &str) -> &str {
+    match lang {
+        "auto" | "en" => "eng",
+        "ar" => "ara",
+        "cs" => "ces",
+        "da" => "dan",
+        "de" => "deu",
+        "el" => "ell",
+        "es" => "spa",
+        "fi" => "fin",
+        "fr" => "fra",
+        "he" | "iw" => "heb",
+        "hi" => "hin",
+        "hu" => "hun",
+        "id" => "ind",
+        "it" => "ita",
+        "ja" => "jpn",
+        "ko" => "kor",
+        "nl" => "nld",
+        "no" => "nor",
+        "pl" => "pol",
+        "pt" => "por",
+        "ro" => "ron",
+        "ru" => "rus",
+        "sv" => "swe",
+        "th" => "tha",
+        "tr" => "tur",
+        "uk" => "ukr",
+        "vi" => "vie",
+        "zh-CN" | "zh" => "chi_sim",
+        "zh-TW" => "chi_tra",
+        lang => lang,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tesseract_lang_of_google_codes() {
+        for (lang, tesseract) in [
+            ("en", "eng"),
+            ("de", "deu"),
+            ("fr", "fra"),
+            ("ja", "jpn"),
+            ("he", "heb"),
+            ("iw", "heb"),
+            ("zh", "chi_sim"),
+            ("zh-CN", "chi_sim"),
+            ("zh-TW", "chi_tra"),
+        ] {
+            assert_eq!(tesseract_lang(lang), tesseract, "{}", lang);
+        }
+    }
+
+    #[test]
+    fn tesseract_lang_of_auto() {
+        assert_eq!(tesseract_lang("auto"), "eng");
+    }
+
+    #[test]
+    fn tesseract_lang_of_unknown_codes() {
+        // Given as they are, so that Tesseract languages can be given directly.
+        assert_eq!(tesseract_lang("eng+jpn"), "eng+jpn");
+        assert_eq!(tesseract_lang("xx"), "xx");
+        assert_eq!(tesseract_lang("EN"), "EN");
+    }
+
+    #[cfg(not(feature = "ocr"))]
+    #[test]
+    fn recognize_without_the_feature() {
+        let err = recognize_image(&[], "eng").unwrap_err();
+
+        assert!(err.to_string().contains("`ocr` feature"), "{}", err);
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::translator::{Backend, Translation};
+
+/// How the translations are printed without a window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// The translation alone
+    #[default]
+    Text,
+    /// A JSON object with the source, the result, the language detected, the alternatives, the
+    /// backend, the latency in milliseconds and the error
+    Json,
+}
+
+/// Outcome of a translation printed as JSON, with either a `result` or an `error`.
+#[derive(Debug, Serialize)]
+pub struct JsonTranslation<'a> {
+    /// File the source was read from by the batch command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<&'a Path>,
+    pub source: &'a str,
+    pub result: Option<&'a str>,
+    pub detected_lang: Option<&'a str>,
+    pub alternatives: &'a [String],
+    pub backend: Backend,
+    pub latency_ms: u128,
+    pub error: Option<String>,
+}
+
+impl<'a> JsonTranslation<'a> {
+    pub fn new(
+        source: &'a str,
+        translated: &'a anyhow::Result<Translation>,
+        backend: Backend,
+        latency: Duration,
+    ) -> Self {
+        let translation = translated.as_ref().ok();
+
+        Self {
+            file: None,
+            source,
+            result: translation.map(|translation| translation.text.as_str()),
+            detected_lang: translation.and_then(|translation| translation.detected_lang.as_deref()),
+            alternatives: translation.map_or(&[], |translation| &translation.alternatives),
+            backend,
+            latency_ms: latency.as_millis(),
+            error: translated.as_ref().err().map(|err| format!("{:#}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_translation() {
+        let translated = Ok(Translation {
+            text: "Guten Morgen".to_string(),
+            detected_lang: Some("en".to_string()),
+            alternatives: vec!["Morgen".to_string()],
+        });
+
+        let json = JsonTranslation::new(
+            "Good morning",
+            &translated,
+            Backend::Google,
+            Duration::from_micros(182_900),
+        );
+
+        assert_eq!(
+            serde_json::to_string(&json).unwrap(),
+            r#"{"source":"Good morning","result":"Guten Morgen","detected_lang":"en","alternatives":["Morgen"],"backend":"google","latency_ms":182,"error":null}"#
+        );
+    }
+
+    #[test]
+    fn json_translation_error() {
+        let translated = Err(anyhow::anyhow!("Connection refused").context("Failed to reach it"));
+
+        let json = JsonTranslation {
+            file: Some(Path::new("notes/a.txt")),
+            ..JsonTranslation::new("Hallo", &translated, Backend::Llm, Duration::ZERO)
+        };
+
+        assert_eq!(
+            serde_json::to_string(&json).unwrap(),
+            r#"{"file":"notes/a.txt","source":"Hallo","result":null,"detected_lang":null,"alternatives":[],"backend":"llm","latency_ms":0,"error":"Failed to reach it: Connection refused"}"#
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use crate::glossary::Glossary;
+use crate::translator::{Translation, Translator};
+use crate::window::TranslationWindowConfig;
+
+/// Parts of the text shielded from the translators, the earlier alternatives win.
+const SHIELDED: &[&str] = &[
+    // Code blocks and inline code.
+    r"(?s:```.*?```)",
+    r"`[^`\n]+`",
+    // URLs, without the punctuation ending the sentence.
+    r#"\b(?:https?|ftp|file)://[^\s<>"']*[^\s<>"'.,;:!?)\]]"#,
+    // Absolute, relative and home paths, but not `km/h` nor `and/or`.
+    r"(?-u:\B)(?:~|\.\.?)?(?:/[\w.-]*[\w-])+/?",
+    r"\b[A-Za-z]:\\[^\s]+",
+    // Syntax of the ICU plural and select messages, around the text of their branches.
+    r"\{\s*\w+\s*,\s*(?:plural|select|selectordinal)\s*,(?:\s*offset:\s*\d+)?\s*(?:=\d+|\w+)\s*\{",
+    r"\}\s*(?:=\d+|\w+)\s*\{",
+    r"\}\s*\}",
+    // Format strings of Python, Rust, C and shells.
+    r"\{[\w.:<>^+#-]*\}",
+    r"%(?:\d+\$|\(\w+\))?[-+#0]*\d*(?:\.\d+)?[sdifuxXoeEgGcp%]",
+    r"\$\{\w+\}",
+    // HTML tags and entities, targets of Markdown links and bold markers.
+    r"</?[A-Za-z][\w-]*(?:\s[^<>]*)?/?>",
+    r"&(?:\w+|#\d+);",
+    r"\]\([^)\s]+\)",
+    r"\*\*",
+];
+
+/// Replaces the code, URLs, paths, format placeholders and markup of `text` with placeholders
+/// standing for themselves, so that they come back untouched.
+pub fn shield(text: &str, placeholders: &mut Placeholders) -> String {
+    regex::Regex::new(&SHIELDED.join("|"))
+        .expect("The shielding regexes need to be valid.")
+        .replace_all(text, |captures: &regex::Captures| {
+            placeholders.add(&captures[0])
+        })
+        .into_owned()
+}
+
+/// Parts of a text swapped for placeholders which the translators leave alone, put back in the
+/// translation.
+#[derive(Debug, Clone, Default)]
+pub struct Placeholders {
+    replacements: Vec<String>,
+}
+
+impl Placeholders {
+    /// Placeholder standing for `replacement` in the text sent to the translator.
+    pub fn add(&mut self, replacement: &str) -> String {
+        let index = match self.replacements.iter().position(|r| r == replacement) {
+            Some(index) => index,
+            None => {
+                self.replacements.push(replacement.to_string());
+                self.replacements.len() - 1
+            }
+        };
+
+        format!("⟦{}⟧", index)
+    }
+
+    /// What the placeholders of the text stand for.
+    pub fn replacements(&self) -> &[String] {
+        &self.replacements
+    }
+
+    /// Puts the replacements back in `translated`, also when the translator added spaces inside
+    /// the placeholders.
+    pub fn restore(&self, translated: &str) -> String {
+        regex::Regex::new(r"⟦\s*(\d+)\s*⟧")
+            .expect("The placeholder regex needs to be valid.")
+            .replace_all(translated, |captures: &regex::Captures| {
+                captures[1]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| self.replacements.get(index))
+                    .cloned()
+                    .unwrap_or_else(|| captures[0].to_string())
+            })
+            .into_owned()
+    }
+
+    /// [`Placeholders::restore`] for a translation still being received, leaving out a
+    /// placeholder cut at its end.
+    pub fn restore_partial(&self, translated: &str) -> String {
+        let complete = match translated.rfind('⟦') {
+            Some(start) if !translated[start..].contains('⟧') => &translated[..start],
+            _ => translated,
+        };
+
+        self.restore(complete)
+    }
+}
+
+/// What is replaced by placeholders in the texts translated from one language to another.
+#[derive(Debug, Clone, Default)]
+pub struct Protection {
+    pub shield: bool,
+    pub glossary: Glossary,
+}
+
+/// Text ready for the translator.
+#[derive(Debug, Clone, Default)]
+pub struct Protected {
+    pub text: String,
+    pub placeholders: Placeholders,
+    /// Translations of the glossary terms found in the text.
+    pub glossary_terms: Vec<String>,
+}
+
+impl Protection {
+    /// Protection of the texts translated from `from` to `to` with `config`, which always shields
+    /// the Markdown.
+    pub fn load(config: &TranslationWindowConfig, from: &str, to: &str) -> anyhow::Result<Self> {
+        let glossary = match &config.glossary_dir {
+            Some(dir) => Glossary::load(dir, from, to)?,
+            None => Glossary::default(),
+        };
+
+        Ok(Self {
+            shield: config.shield || config.markdown,
+            glossary,
+        })
+    }
+
+    /// Shields `text`, then protects its glossary terms.
+    pub fn protect(&self, text: &str) -> Protected {
+        let mut placeholders = Placeholders::default();
+        let text = if self.shield {
+            shield(text, &mut placeholders)
+        } else {
+            text.to_string()
+        };
+        let shielded = placeholders.replacements().len();
+        let text = self.glossary.protect(&text, &mut placeholders);
+        let glossary_terms = placeholders.replacements()[shielded..].to_vec();
+
+        Protected {
+            text,
+            placeholders,
+            glossary_terms,
+        }
+    }
+
+    /// Translates `text` protected with `translator`.
+    pub async fn translate(
+        &self,
+        translator: &impl Translator,
+        from: &str,
+        to: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let protected = self.protect(text);
+        let translated = translator.translate(from, to, &protected.text).await?;
+
+        Ok(protected.placeholders.restore(&translated))
+    }
+
+    /// Like [`Protection::translate`], with the details of [`Translator::translate_detailed`].
+    pub async fn translate_detailed(
+        &self,
+        translator: &impl Translator,
+        from: &str,
+        to: &str,
+        text: &str,
+    ) -> anyhow::Result<Translation> {
+        let protected = self.protect(text);
+        let translation = translator
+            .translate_detailed(from, to, &protected.text)
+            .await?;
+
+        Ok(Translation {
+            text: protected.placeholders.restore(&translation.text),
+            alternatives: translation
+                .alternatives
+                .iter()
+                .map(|alternative| protected.placeholders.restore(alternative))
+                .collect(),
+            ..translation
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What [`shield`] replaces in `text`.
+    fn shielded(text: &str) -> Vec<String> {
+        let mut placeholders = Placeholders::default();
+        shield(text, &mut placeholders);
+        placeholders.replacements().to_vec()
+    }
+
+    #[test]
+    fn urls_without_the_punctuation_after_them() {
+        assert_eq!(
+            shielded("See https://example.com/a?b=1. Or (https://x.org/wiki/Rust), ok?"),
+            ["https://example.com/a?b=1", "https://x.org/wiki/Rust"]
+        );
+        assert_eq!(
+            shielded("Get it at ftp://host/file.tar.gz, or file:///tmp/x!"),
+            ["ftp://host/file.tar.gz", "file:///tmp/x"]
+        );
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            shielded("Edit ~/notes.txt, /etc/hosts. and ./run.sh in ../src/ or C:\\Users\\me"),
+            [
+                "~/notes.txt",
+                "/etc/hosts",
+                "./run.sh",
+                "../src/",
+                "C:\\Users\\me"
+            ]
+        );
+        assert!(shielded("60 km/h and/or 3 m/s, 1/2 of it").is_empty());
+    }
+
+    #[test]
+    fn icu_messages() {
+        let mut placeholders = Placeholders::default();
+
+        let text = shield(
+            "{count, plural, offset:1 =0 {No files} one {# file} other {{count} files}}",
+            &mut placeholders,
+        );
+
+        assert_eq!(text, "⟦0⟧No files⟦1⟧# file⟦2⟧⟦3⟧ files⟦4⟧");
+        assert_eq!(
+            placeholders.replacements(),
+            [
+                "{count, plural, offset:1 =0 {",
+                "} one {",
+                "} other {",
+                "{count}",
+                "}}"
+            ]
+        );
+        assert_eq!(
+            shielded("{gender, select, female {She} other {They}} left"),
+            ["{gender, select, female {", "} other {", "}}"]
+        );
+    }
+
+    #[test]
+    fn format_strings_code_and_markup() {
+        assert_eq!(
+            shielded("{name} {0:.2f} %s %1$d %(user)s %-5.2f 100%% ${HOME} {}"),
+            ["{name}", "{0:.2f}", "%s", "%1$d", "%(user)s", "%-5.2f", "%%", "${HOME}", "{}"]
+        );
+        assert_eq!(
+            shielded("Run `cargo test` then\n```\nlet a = 1;\n```\n"),
+            ["`cargo test`", "```\nlet a = 1;\n```"]
+        );
+        assert_eq!(
+            shielded("<a href=\"x\">**Read** [the docs](https://docs.rs)</a>&nbsp;&#8212;<br/>"),
+            [
+                "<a href=\"x\">",
+                "**",
+                "](https://docs.rs)",
+                "</a>",
+                "&nbsp;",
+                "&#8212;",
+                "<br/>"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_replacement_same_placeholder() {
+        let mut placeholders = Placeholders::default();
+
+        let text = shield("`a` and `b` and `a`", &mut placeholders);
+
+        assert_eq!(text, "⟦0⟧ and ⟦1⟧ and ⟦0⟧");
+        assert_eq!(placeholders.replacements(), ["`a`", "`b`"]);
+    }
+
+    #[test]
+    fn restore() {
+        let mut placeholders = Placeholders::default();
+        placeholders.add("`x`");
+        placeholders.add("{name}");
+
+        assert_eq!(
+            placeholders.restore("⟦1⟧ usa ⟦ 0 ⟧, ⟦0⟧ y ⟦\u{a0}1⟧ pero no ⟦7⟧"),
+            "{name} usa `x`, `x` y {name} pero no ⟦7⟧"
+        );
+    }
+
+    #[test]
+    fn restore_partial() {
+        let mut placeholders = Placeholders::default();
+        placeholders.add("`x`");
+        placeholders.add("{name}");
+
+        assert_eq!(placeholders.restore_partial("Hola ⟦0⟧ y ⟦1"), "Hola `x` y ");
+        assert_eq!(placeholders.restore_partial("Hola ⟦0⟧ y ⟦"), "Hola `x` y ");
+        assert_eq!(
+            placeholders.restore_partial("Hola ⟦0⟧ y ⟦1⟧"),
+            "Hola `x` y {name}"
+        );
+        assert_eq!(placeholders.restore_partial("Hola"), "Hola");
+    }
+
+    #[test]
+    fn protect_with_the_glossary() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("en-es.tsv"), "pull request\tsolicitud\n").unwrap();
+        let protection = Protection {
+            shield: true,
+            glossary: Glossary::load(dir.path(), "en", "es").unwrap(),
+        };
+
+        let protected = protection.protect("Open a pull request for `main`");
+
+        assert_eq!(protected.text, "Open a ⟦1⟧ for ⟦0⟧");
+        assert_eq!(protected.glossary_terms, ["solicitud"]);
+        assert_eq!(
+            protected.placeholders.restore("Abre una ⟦ 1 ⟧ para ⟦0 ⟧"),
+            "Abre una solicitud para `main`"
+        );
+        assert_eq!(
+            Protection::default().protect("`main`").text,
+            "`main`",
+            "Nothing is shielded without `shield`"
+        );
+    }
+}
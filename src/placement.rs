@@ -0,0 +1,160 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::state_dir;
+use crate::env_info::EnvironmentInfo;
+use crate::margins::Margins;
+
+/// Where the translation window is put on the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// Next to the pointer.
+    #[default]
+    UnderCursor,
+    /// Centered on the output.
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// Along the bottom edge of the focused window (sway only), centered on the output otherwise.
+    FocusedWindow,
+    /// Same position as the previous window, under the cursor if there was none.
+    LastUsed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Placement {
+    /// Margins for a `width`x`height` window, `cursor_offset` is the gap between the pointer and
+    /// the window for [`Placement::UnderCursor`].
+    pub fn margins(
+        self,
+        env_info: &EnvironmentInfo,
+        width: i32,
+        height: i32,
+        cursor_offset: i32,
+    ) -> Margins {
+        let output_width = env_info.monitor_width;
+        let output_height = env_info.monitor_height;
+        let at = |x, y| Margins::at(output_width, output_height, x, y, width, height);
+
+        match self {
+            Placement::UnderCursor => Margins::under_cursor(
+                output_width,
+                output_height,
+                env_info.pointer_x,
+                env_info.pointer_y,
+                width,
+                height,
+                cursor_offset,
+            ),
+            Placement::Center => at((output_width - width) / 2, (output_height - height) / 2),
+            Placement::TopLeft => at(0, 0),
+            Placement::TopRight => at(output_width - width, 0),
+            Placement::BottomLeft => at(0, output_height - height),
+            Placement::BottomRight => at(output_width - width, output_height - height),
+            Placement::FocusedWindow => match focused_window_rect() {
+                Some(rect) => at(
+                    rect.x + (rect.width - width) / 2,
+                    rect.y + rect.height - height,
+                ),
+                None => at((output_width - width) / 2, (output_height - height) / 2),
+            },
+            Placement::LastUsed => match load_last_used() {
+                Some(last) => at(last.left, last.top),
+                None => Placement::UnderCursor.margins(env_info, width, height, cursor_offset),
+            },
+        }
+    }
+}
+
+/// Remembers `margins` for the next [`Placement::LastUsed`] window, failures are ignored.
+pub fn save_last_used(margins: Margins) {
+    let path = match last_used_path() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(
+        path,
+        format!(
+            "{} {} {} {}\n",
+            margins.top, margins.right, margins.bottom, margins.left
+        ),
+    );
+}
+
+fn load_last_used() -> Option<Margins> {
+    let content = std::fs::read_to_string(last_used_path()?).ok()?;
+    let mut values = content.split_whitespace().map(|value| value.parse().ok());
+    Some(Margins {
+        top: values.next()??,
+        right: values.next()??,
+        bottom: values.next()??,
+        left: values.next()??,
+    })
+}
+
+fn last_used_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("last_position"))
+}
+
+/// Asks sway for the focused window, in coordinates relative to its output.
+fn focused_window_rect() -> Option<Rect> {
+    const IPC_MAGIC: &[u8] = b"i3-ipc";
+    const GET_TREE: u32 = 4;
+
+    let mut stream = UnixStream::connect(std::env::var_os("SWAYSOCK")?).ok()?;
+    let mut request = IPC_MAGIC.to_vec();
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(&GET_TREE.to_ne_bytes());
+    stream.write_all(&request).ok()?;
+
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header).ok()?;
+    if &header[..6] != IPC_MAGIC {
+        return None;
+    }
+    let length = u32::from_ne_bytes(header[6..10].try_into().ok()?);
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload).ok()?;
+
+    let tree: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    find_focused(&tree, None)
+}
+
+fn find_focused(node: &serde_json::Value, output: Option<Rect>) -> Option<Rect> {
+    let rect = Rect::deserialize(node.get("rect")?).ok()?;
+    let output = match node.get("type").and_then(|t| t.as_str()) {
+        Some("output") => Some(rect),
+        _ => output,
+    };
+
+    if node.get("focused").and_then(|f| f.as_bool()) == Some(true) {
+        let output = output?;
+        return Some(Rect {
+            x: rect.x - output.x,
+            y: rect.y - output.y,
+            ..rect
+        });
+    }
+
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key)?.as_array())
+        .flatten()
+        .find_map(|child| find_focused(child, output))
+}
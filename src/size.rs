@@ -0,0 +1,379 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Length in pixels, or in percent of the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(i32),
+    Percent(f64),
+}
+
+impl Length {
+    pub fn resolve(self, output_length: i32) -> i32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (output_length as f64 * percent / 100.0).round() as i32,
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{}", pixels),
+            Length::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let length = match s.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| percent.is_finite() && *percent >= 0.0)
+                .map(Length::Percent),
+            None => s
+                .parse::<i32>()
+                .ok()
+                .filter(|pixels| *pixels >= 0)
+                .map(Length::Pixels),
+        };
+
+        length.ok_or_else(|| {
+            format!(
+                "invalid length `{}`, expected pixels like `600` or a percentage like `25%`",
+                s
+            )
+        })
+    }
+}
+
+/// `WIDTHxHEIGHT`, or a single length used for both.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dimensions {
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Dimensions {
+    pub const fn percent(width: f64, height: f64) -> Self {
+        Self {
+            width: Length::Percent(width),
+            height: Length::Percent(height),
+        }
+    }
+
+    pub fn resolve(self, output_width: i32, output_height: i32) -> (i32, i32) {
+        (
+            self.width.resolve(output_width),
+            self.height.resolve(output_height),
+        )
+    }
+}
+
+impl fmt::Display for Dimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for Dimensions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('x') {
+            Some((width, height)) => Ok(Self {
+                width: width.parse()?,
+                height: height.parse()?,
+            }),
+            None => {
+                let length = s.parse()?;
+                Ok(Self {
+                    width: length,
+                    height: length,
+                })
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Dimensions {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Dimensions> for String {
+    fn from(dimensions: Dimensions) -> Self {
+        dimensions.to_string()
+    }
+}
+
+/// Size of the translation window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum WindowSize {
+    /// Just big enough for the text.
+    Auto,
+    Fixed(Dimensions),
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        WindowSize::Fixed(Dimensions::percent(25.0, 25.0))
+    }
+}
+
+impl WindowSize {
+    /// Size of the window on a `output_width`x`output_height` output, kept between `min` and `max`.
+    ///
+    /// `content_size` measures the window needed to show the text without scrolling when it may
+    /// be at most the given width, it is only called for [`WindowSize::Auto`].
+    pub fn resolve(
+        self,
+        output_width: i32,
+        output_height: i32,
+        min: Dimensions,
+        max: Dimensions,
+        content_size: impl FnOnce(i32) -> (i32, i32),
+    ) -> (i32, i32) {
+        let (min_width, min_height) = min.resolve(output_width, output_height);
+        let (max_width, max_height) = max.resolve(output_width, output_height);
+        let max_width = max_width.max(min_width);
+        let max_height = max_height.max(min_height);
+
+        let (width, height) = match self {
+            WindowSize::Auto => content_size(max_width),
+            WindowSize::Fixed(dimensions) => dimensions.resolve(output_width, output_height),
+        };
+
+        (
+            width.clamp(min_width, max_width),
+            height.clamp(min_height, max_height),
+        )
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowSize::Auto => write!(f, "auto"),
+            WindowSize::Fixed(dimensions) => write!(f, "{}", dimensions),
+        }
+    }
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "auto" {
+            Ok(WindowSize::Auto)
+        } else {
+            s.parse().map(WindowSize::Fixed)
+        }
+    }
+}
+
+impl TryFrom<String> for WindowSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<WindowSize> for String {
+    fn from(size: WindowSize) -> Self {
+        size.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn no_content(_: i32) -> (i32, i32) {
+        panic!("Only an auto size measures its content")
+    }
+
+    #[test]
+    fn parse_lengths() {
+        assert_eq!("600".parse(), Ok(Length::Pixels(600)));
+        assert_eq!(" 0 ".parse(), Ok(Length::Pixels(0)));
+        assert_eq!("25%".parse(), Ok(Length::Percent(25.0)));
+        assert_eq!("12.5 %".parse(), Ok(Length::Percent(12.5)));
+        for invalid in ["", "-1", "-5%", "NaN%", "inf%", "1.5", "px", "10px"] {
+            assert!(invalid.parse::<Length>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resolve_lengths() {
+        assert_eq!(Length::Pixels(600).resolve(1920), 600);
+        assert_eq!(Length::Percent(25.0).resolve(1920), 480);
+        assert_eq!(Length::Percent(25.0).resolve(1366), 342);
+        assert_eq!(Length::Percent(150.0).resolve(1000), 1500);
+    }
+
+    #[test]
+    fn parse_dimensions() {
+        assert_eq!(
+            "600x400".parse(),
+            Ok(Dimensions {
+                width: Length::Pixels(600),
+                height: Length::Pixels(400),
+            })
+        );
+        assert_eq!(
+            "50%x300".parse::<Dimensions>().unwrap().to_string(),
+            "50%x300"
+        );
+        assert_eq!("40%".parse(), Ok(Dimensions::percent(40.0, 40.0)));
+        assert!("600x".parse::<Dimensions>().is_err());
+        assert!("600x400x200".parse::<Dimensions>().is_err());
+        assert_eq!(
+            serde_json::from_str::<Dimensions>(r#""30%x200""#).unwrap(),
+            Dimensions {
+                width: Length::Percent(30.0),
+                height: Length::Pixels(200),
+            }
+        );
+        assert!(serde_json::from_str::<Dimensions>(r#""big""#).is_err());
+    }
+
+    #[test]
+    fn parse_window_sizes() {
+        assert_eq!("auto".parse(), Ok(WindowSize::Auto));
+        assert_eq!(" auto ".parse(), Ok(WindowSize::Auto));
+        assert_eq!(
+            "25%x10%".parse(),
+            Ok(WindowSize::Fixed(Dimensions::percent(25.0, 10.0)))
+        );
+        assert!("Auto".parse::<WindowSize>().is_err());
+        assert_eq!(WindowSize::default().to_string(), "25%x25%");
+        assert_eq!(
+            serde_json::to_string(&WindowSize::Auto).unwrap(),
+            r#""auto""#
+        );
+    }
+
+    #[test]
+    fn fixed_size() {
+        let size = "50%x200".parse::<WindowSize>().unwrap();
+
+        assert_eq!(
+            size.resolve(
+                1920,
+                1080,
+                Dimensions::percent(0.0, 0.0),
+                Dimensions::percent(100.0, 100.0),
+                no_content
+            ),
+            (960, 200)
+        );
+    }
+
+    #[test]
+    fn fixed_size_is_clamped() {
+        let size = "5000x10".parse::<WindowSize>().unwrap();
+
+        assert_eq!(
+            size.resolve(
+                1920,
+                1080,
+                "100x50".parse().unwrap(),
+                Dimensions::percent(50.0, 50.0),
+                no_content
+            ),
+            (960, 50)
+        );
+    }
+
+    #[test]
+    fn auto_size_measures_up_to_the_max_width() {
+        let mut measured_width = None;
+
+        let size = WindowSize::Auto.resolve(
+            1920,
+            1080,
+            "200x40".parse().unwrap(),
+            Dimensions::percent(50.0, 50.0),
+            |max_width| {
+                measured_width = Some(max_width);
+                (120, 2000)
+            },
+        );
+
+        assert_eq!(measured_width, Some(960));
+        assert_eq!(size, (200, 540));
+    }
+
+    #[test]
+    fn min_larger_than_max() {
+        let size = WindowSize::Auto.resolve(
+            1920,
+            1080,
+            "800x600".parse().unwrap(),
+            "400x300".parse().unwrap(),
+            |max_width| (max_width, 100),
+        );
+
+        assert_eq!(size, (800, 600));
+    }
+
+    fn lengths() -> impl Strategy<Value = Length> {
+        prop_oneof![
+            (0..5000).prop_map(Length::Pixels),
+            (0..=200u32).prop_map(|percent| Length::Percent(f64::from(percent) / 2.0)),
+        ]
+    }
+
+    fn dimensions() -> impl Strategy<Value = Dimensions> {
+        (lengths(), lengths()).prop_map(|(width, height)| Dimensions { width, height })
+    }
+
+    proptest! {
+        #[test]
+        fn display_round_trips(dimensions in dimensions(), auto in any::<bool>()) {
+            let size = if auto { WindowSize::Auto } else { WindowSize::Fixed(dimensions) };
+
+            prop_assert_eq!(size.to_string().parse::<WindowSize>(), Ok(size));
+        }
+
+        #[test]
+        fn resolve_stays_within_min_and_max(
+            output_width in 1..8000,
+            output_height in 1..8000,
+            fixed in dimensions(),
+            content in (0..10000, 0..10000),
+            min in dimensions(),
+            max in dimensions(),
+            auto in any::<bool>(),
+        ) {
+            let size = if auto { WindowSize::Auto } else { WindowSize::Fixed(fixed) };
+            let (min_width, min_height) = min.resolve(output_width, output_height);
+            let (max_width, max_height) = max.resolve(output_width, output_height);
+
+            let (width, height) =
+                size.resolve(output_width, output_height, min, max, |_| content);
+
+            prop_assert!(width >= min_width && height >= min_height);
+            prop_assert!(width <= max_width.max(min_width));
+            prop_assert!(height <= max_height.max(min_height));
+        }
+    }
+}
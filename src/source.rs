@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::fd::{AsFd, BorrowedFd};
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use wayland_client::{
+    backend::ObjectId,
+    delegate_noop, event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+};
+
+/// Text MIME types in order of preference.
+const TEXT_MIME_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+/// Where the text to translate comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// The `--src-text` argument.
+    Arg,
+    Stdin,
+    /// The Wayland clipboard.
+    Clipboard,
+    /// The Wayland primary selection, i.e. the selected text.
+    Primary,
+}
+
+impl Source {
+    pub fn read(self, src_text: Option<String>) -> anyhow::Result<String> {
+        match self {
+            Source::Arg => {
+                src_text.ok_or_else(|| anyhow!("--src-text is required with --source arg"))
+            }
+            Source::Stdin => {
+                let mut text = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut text)
+                    .context("Failed to read stdin")?;
+                Ok(text)
+            }
+            Source::Clipboard => read_selection(false),
+            Source::Primary => read_selection(true),
+        }
+    }
+}
+
+/// Selection offered through ext-data-control or wlr-data-control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Offer {
+    Ext(ExtDataControlOfferV1),
+    Wlr(ZwlrDataControlOfferV1),
+}
+
+impl Offer {
+    fn id(&self) -> ObjectId {
+        match self {
+            Offer::Ext(offer) => offer.id(),
+            Offer::Wlr(offer) => offer.id(),
+        }
+    }
+
+    fn receive(&self, mime_type: String, fd: BorrowedFd) {
+        match self {
+            Offer::Ext(offer) => offer.receive(mime_type, fd),
+            Offer::Wlr(offer) => offer.receive(mime_type, fd),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Offer::Ext(offer) => offer.destroy(),
+            Offer::Wlr(offer) => offer.destroy(),
+        }
+    }
+}
+
+enum Device {
+    Ext(ExtDataControlDeviceV1),
+    Wlr(ZwlrDataControlDeviceV1),
+}
+
+#[derive(Default)]
+struct State {
+    mime_types: HashMap<ObjectId, Vec<String>>,
+    selection: Option<Offer>,
+    primary_selection: Option<Offer>,
+    primary_selection_changed: bool,
+}
+
+impl State {
+    /// Replaces the clipboard, or primary selection if `primary`, destroying the previous offer.
+    fn replace_offer(&mut self, primary: bool, offer: Option<Offer>) {
+        let (slot, other) = if primary {
+            (&mut self.primary_selection, &self.selection)
+        } else {
+            (&mut self.selection, &self.primary_selection)
+        };
+        let previous = std::mem::replace(slot, offer);
+        if let Some(previous) = &previous {
+            if other.as_ref() != Some(previous) && slot.as_ref() != Some(previous) {
+                self.mime_types.remove(&previous.id());
+                previous.destroy();
+            }
+        }
+    }
+}
+
+/// Data device of the first seat, bound through ext-data-control, or wlr-data-control on the
+/// compositors without it, so that no window needs to have focus.
+struct DataControl {
+    conn: Connection,
+    event_queue: EventQueue<State>,
+    state: State,
+    device: Device,
+}
+
+impl DataControl {
+    fn connect(primary: bool) -> anyhow::Result<Self> {
+        let conn =
+            Connection::connect_to_env().context("Failed to connect to the Wayland display")?;
+        let (globals, mut event_queue) = registry_queue_init::<State>(&conn)?;
+        let qh = event_queue.handle();
+
+        let seat = globals
+            .bind::<WlSeat, _, _>(&qh, 1..=1, ())
+            .context("wl_seat is not available")?;
+        let device = match globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ()) {
+            Ok(manager) => Device::Ext(manager.get_data_device(&seat, &qh, ())),
+            Err(_) => {
+                let manager = globals
+                    .bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ())
+                    .context(
+                        "The compositor supports neither ext-data-control nor wlr-data-control",
+                    )?;
+                if primary && manager.version() < 2 {
+                    return Err(anyhow!(
+                        "The compositor does not support reading the primary selection"
+                    ));
+                }
+                Device::Wlr(manager.get_data_device(&seat, &qh, ()))
+            }
+        };
+
+        let mut state = State::default();
+        event_queue.roundtrip(&mut state)?;
+
+        Ok(Self {
+            conn,
+            event_queue,
+            state,
+            device,
+        })
+    }
+
+    /// Current clipboard, or primary selection if `primary`.
+    fn selection(&self, primary: bool) -> Option<Offer> {
+        if primary {
+            self.state.primary_selection.clone()
+        } else {
+            self.state.selection.clone()
+        }
+    }
+
+    fn read(&self, offer: &Offer) -> anyhow::Result<String> {
+        let offered = self
+            .state
+            .mime_types
+            .get(&offer.id())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mime_type = TEXT_MIME_TYPES
+            .iter()
+            .find(|mime_type| offered.iter().any(|offered| offered == *mime_type))
+            .ok_or_else(|| anyhow!("The selection does not contain text"))?;
+
+        let (mut reader, writer) = std::io::pipe()?;
+        offer.receive(mime_type.to_string(), writer.as_fd());
+        drop(writer);
+        self.conn.flush()?;
+
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .context("Failed to read the selection")?;
+
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+}
+
+impl Drop for DataControl {
+    fn drop(&mut self) {
+        match &self.device {
+            Device::Ext(device) => device.destroy(),
+            Device::Wlr(device) => device.destroy(),
+        }
+        let _ = self.conn.flush();
+    }
+}
+
+/// Reads the clipboard, or the primary selection if `primary`.
+fn read_selection(primary: bool) -> anyhow::Result<String> {
+    let data_control = DataControl::connect(primary)?;
+
+    match data_control.selection(primary) {
+        Some(offer) => data_control.read(&offer),
+        None => Ok(String::new()),
+    }
+}
+
+/// Calls `on_change` with the text of every new primary selection, until the connection fails.
+pub fn watch_primary_selection(mut on_change: impl FnMut(String)) -> anyhow::Result<()> {
+    let mut data_control = DataControl::connect(true)?;
+    data_control.state.primary_selection_changed = false;
+
+    loop {
+        data_control
+            .event_queue
+            .blocking_dispatch(&mut data_control.state)?;
+        if !std::mem::take(&mut data_control.state.primary_selection_changed) {
+            continue;
+        }
+
+        if let Some(offer) = data_control.selection(true) {
+            match data_control.read(&offer) {
+                Ok(text) => on_change(text),
+                Err(err) => eprintln!("Error: {:#}", err),
+            }
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _device: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_device_v1::Event::DataOffer { id } => {
+                state.mime_types.insert(id.id(), Vec::new());
+            }
+            zwlr_data_control_device_v1::Event::Selection { id } => {
+                state.replace_offer(false, id.map(Offer::Wlr));
+            }
+            zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.replace_offer(true, id.map(Offer::Wlr));
+                state.primary_selection_changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwlrDataControlOfferV1,
+        event: zwlr_data_control_offer_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+            state
+                .mime_types
+                .entry(offer.id())
+                .or_default()
+                .push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _device: &ExtDataControlDeviceV1,
+        event: ext_data_control_device_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_data_control_device_v1::Event::DataOffer { id } => {
+                state.mime_types.insert(id.id(), Vec::new());
+            }
+            ext_data_control_device_v1::Event::Selection { id } => {
+                state.replace_offer(false, id.map(Offer::Ext));
+            }
+            ext_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.replace_offer(true, id.map(Offer::Ext));
+                state.primary_selection_changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ExtDataControlDeviceV1, [
+        ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ExtDataControlOfferV1,
+        event: ext_data_control_offer_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_data_control_offer_v1::Event::Offer { mime_type } = event {
+            state
+                .mime_types
+                .entry(offer.id())
+                .or_default()
+                .push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wayland_client::protocol::wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(State: ExtDataControlManagerV1);
+delegate_noop!(State: ZwlrDataControlManagerV1);
+delegate_noop!(State: ignore WlSeat);
@@ -0,0 +1,316 @@
+use crate::placeholder::Protection;
+use crate::translator::Translator;
+
+/// Longest text sent in one request, in bytes, whatever the number of cues.
+const MAX_BATCH_LEN: usize = 4000;
+
+/// Block of an SRT or WebVTT file, the blocks are separated by empty lines.
+#[derive(Debug, Clone)]
+enum Block {
+    /// WebVTT header, note, style or region, copied as it is.
+    Kept(String),
+    Cue {
+        /// Number or identifier of the cue, and its timing and settings.
+        header: String,
+        text: String,
+    },
+}
+
+/// Translates the text of the cues of the SRT or WebVTT `subtitles`, `batch_size` cues per
+/// request, keeping their numbers, timings and settings, and the other blocks as they are.
+pub async fn translate(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    subtitles: &str,
+    protection: &Protection,
+    batch_size: usize,
+) -> anyhow::Result<String> {
+    let bom = subtitles.starts_with('\u{feff}');
+    let crlf = subtitles.contains("\r\n");
+    let subtitles = subtitles
+        .trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n");
+
+    let mut blocks = parse(&subtitles);
+    let texts: Vec<&mut String> = blocks
+        .iter_mut()
+        .filter_map(|block| match block {
+            Block::Cue { text, .. } if !text.trim().is_empty() => Some(text),
+            _ => None,
+        })
+        .collect();
+
+    let mut batch: Vec<&mut String> = Vec::new();
+    let mut batch_len = 0;
+    for text in texts {
+        if !batch.is_empty()
+            && (batch.len() >= batch_size.max(1) || batch_len + text.len() > MAX_BATCH_LEN)
+        {
+            translate_batch(translator, from, to, protection, &mut batch).await?;
+            batch.clear();
+            batch_len = 0;
+        }
+        batch_len += text.len() + 2;
+        batch.push(text);
+    }
+    translate_batch(translator, from, to, protection, &mut batch).await?;
+
+    let mut translated = if bom {
+        "\u{feff}".to_string()
+    } else {
+        String::new()
+    };
+    for (index, block) in blocks.iter().enumerate() {
+        if index > 0 {
+            translated.push('\n');
+        }
+        match block {
+            Block::Kept(lines) => translated.push_str(lines),
+            Block::Cue { header, text } => {
+                translated.push_str(header);
+                if !text.is_empty() {
+                    translated.push_str(text);
+                    translated.push('\n');
+                }
+            }
+        }
+    }
+    if crlf {
+        translated = translated.replace('\n', "\r\n");
+    }
+
+    Ok(translated)
+}
+
+fn parse(subtitles: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut lines = subtitles.lines().peekable();
+    while lines.peek().is_some() {
+        let mut block = Vec::new();
+        while let Some(line) = lines.next_if(|line| !line.trim().is_empty()) {
+            block.push(line);
+        }
+        while lines.next_if(|line| line.trim().is_empty()).is_some() {}
+        if block.is_empty() {
+            continue;
+        }
+
+        // The first block of a WebVTT file holds the header, not a cue, even if it has a timing.
+        let timing = block.iter().position(|line| line.contains("-->"));
+        blocks.push(match timing {
+            Some(timing) if !block[0].starts_with("WEBVTT") && !block[0].starts_with("NOTE") => {
+                Block::Cue {
+                    header: block[..=timing]
+                        .iter()
+                        .map(|line| format!("{}\n", line))
+                        .collect(),
+                    text: block[timing + 1..].join("\n"),
+                }
+            }
+            _ => Block::Kept(block.iter().map(|line| format!("{}\n", line)).collect()),
+        });
+    }
+
+    blocks
+}
+
+/// Translates the cue texts of `batch` in one request, separated by empty lines, or one by one
+/// if the translation does not have as many parts.
+async fn translate_batch(
+    translator: &impl Translator,
+    from: &str,
+    to: &str,
+    protection: &Protection,
+    batch: &mut [&mut String],
+) -> anyhow::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let joined = batch
+        .iter()
+        .map(|text| text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let translated = protection.translate(translator, from, to, &joined).await?;
+    let parts: Vec<&str> = regex::Regex::new(r"\n[ \t]*\n")
+        .expect("The cue separator regex needs to be valid.")
+        .split(translated.trim())
+        .collect();
+    if parts.len() == batch.len() {
+        for (text, part) in batch.iter_mut().zip(parts) {
+            **text = part.trim().to_string();
+        }
+        return Ok(());
+    }
+
+    for text in batch.iter_mut() {
+        let translated = protection.translate(translator, from, to, text).await?;
+        // An empty line would end the cue.
+        **text = translated
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Answers with `answer` of the text, and keeps the texts it was asked to translate.
+    struct FakeTranslator<F> {
+        answer: F,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl<F: Fn(&str) -> String + Sync> FakeTranslator<F> {
+        fn new(answer: F) -> Self {
+            Self {
+                answer,
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl<F: Fn(&str) -> String + Sync> Translator for FakeTranslator<F> {
+        async fn translate(&self, _from: &str, _to: &str, text: &str) -> anyhow::Result<String> {
+            self.requests.lock().unwrap().push(text.to_string());
+            Ok((self.answer)(text))
+        }
+    }
+
+    async fn translate_with(
+        translator: &FakeTranslator<impl Fn(&str) -> String + Sync>,
+        subtitles: &str,
+        batch_size: usize,
+    ) -> String {
+        translate(
+            translator,
+            "en",
+            "fr",
+            subtitles,
+            &Protection::default(),
+            batch_size,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn srt() {
+        let translator = FakeTranslator::new(str::to_uppercase);
+
+        let translated = translate_with(
+            &translator,
+            "1\n00:00:01,000 --> 00:00:02,500\nHello\nworld\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\n<i>Bye</i>\n",
+            10,
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "1\n00:00:01,000 --> 00:00:02,500\nHELLO\nWORLD\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\n<I>BYE</I>\n"
+        );
+        assert_eq!(translator.requests(), ["Hello\nworld\n\n<i>Bye</i>"]);
+    }
+
+    #[tokio::test]
+    async fn vtt() {
+        let translator = FakeTranslator::new(str::to_uppercase);
+        let subtitles = "WEBVTT - Some title\n\n\
+                         NOTE written by\nsomeone --> else\n\n\
+                         STYLE\n::cue { color: yellow }\n\n\
+                         intro\n00:01.000 --> 00:02.000 align:start line:0\nHi\n\n\
+                         00:03.000 --> 00:04.000\n";
+
+        let translated = translate_with(&translator, subtitles, 10).await;
+
+        assert_eq!(
+            translated,
+            "WEBVTT - Some title\n\n\
+             NOTE written by\nsomeone --> else\n\n\
+             STYLE\n::cue { color: yellow }\n\n\
+             intro\n00:01.000 --> 00:02.000 align:start line:0\nHI\n\n\
+             00:03.000 --> 00:04.000\n"
+        );
+        assert_eq!(translator.requests(), ["Hi"]);
+    }
+
+    #[tokio::test]
+    async fn bom_and_crlf() {
+        let translator = FakeTranslator::new(str::to_uppercase);
+
+        let translated = translate_with(
+            &translator,
+            "\u{feff}1\r\n00:00:01,000 --> 00:00:02,000\r\nHello\r\nworld\r\n\r\n\
+             2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n",
+            10,
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "\u{feff}1\r\n00:00:01,000 --> 00:00:02,000\r\nHELLO\r\nWORLD\r\n\r\n\
+             2\r\n00:00:03,000 --> 00:00:04,000\r\nBYE\r\n"
+        );
+        assert_eq!(translator.requests(), ["Hello\nworld\n\nBye"]);
+    }
+
+    #[tokio::test]
+    async fn batches() {
+        let translator = FakeTranslator::new(str::to_uppercase);
+        let subtitles: String = (1..=5)
+            .map(|cue| {
+                format!(
+                    "{}\n00:00:0{},000 --> 00:00:0{},500\ncue {}\n\n",
+                    cue, cue, cue, cue
+                )
+            })
+            .collect();
+
+        translate_with(&translator, &subtitles, 2).await;
+
+        assert_eq!(
+            translator.requests(),
+            ["cue 1\n\ncue 2", "cue 3\n\ncue 4", "cue 5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn one_by_one_when_the_parts_do_not_match() {
+        // Doubling the line breaks makes more parts than cues, and empty lines in the cues.
+        let translator =
+            FakeTranslator::new(|text: &str| text.to_uppercase().replace('\n', "\n\n"));
+
+        let translated = translate_with(
+            &translator,
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\nworld\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nBye\n",
+            10,
+        )
+        .await;
+
+        assert_eq!(
+            translated,
+            "1\n00:00:01,000 --> 00:00:02,000\nHELLO\nWORLD\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nBYE\n"
+        );
+        assert_eq!(
+            translator.requests(),
+            ["Hello\nworld\n\nBye", "Hello\nworld", "Bye"]
+        );
+    }
+}
@@ -1,8 +1,263 @@
-use anyhow::anyhow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::config::LlmConfig;
+
+const DEFAULT_LLM_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_LLM_PROMPT: &str = "You are a translation engine. Translate the text of the user \
+from {from} to {to}. {context}
+Answer with the translation only, without quotes, notes or explanations.";
 
 pub trait Translator {
     async fn translate(&self, from_lang: &str, to_lang: &str, text: &str)
         -> anyhow::Result<String>;
+
+    /// Like [`Translator::translate`], but also calls `on_chunk` with the pieces of the translation
+    /// as soon as they arrive. Put together they make the translation, up to surrounding whitespace.
+    ///
+    /// Translators which cannot stream call `on_chunk` once with the whole translation.
+    async fn translate_streaming(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        mut on_chunk: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<String> {
+        let translation = self.translate(from_lang, to_lang, text).await?;
+        on_chunk(&translation);
+
+        Ok(translation)
+    }
+
+    /// Like [`Translator::translate`], but also tells the language detected and other possible
+    /// translations of the text.
+    ///
+    /// Translators which cannot tell them only give the translation.
+    async fn translate_detailed(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<Translation> {
+        let translation = self.translate(from_lang, to_lang, text).await?;
+
+        Ok(Translation {
+            text: translation,
+            ..Default::default()
+        })
+    }
+}
+
+/// Translation with what the translator tells about it.
+#[derive(Debug, Clone, Default)]
+pub struct Translation {
+    pub text: String,
+    /// Language of the text, detected when translating from `auto`.
+    pub detected_lang: Option<String>,
+    /// Other ways to translate the text.
+    pub alternatives: Vec<String>,
+}
+
+/// Translation service used by the windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// Google Translate, needs a network connection.
+    #[default]
+    Google,
+    /// Local OPUS-MT/Marian models run on the CPU by CTranslate2.
+    Offline,
+    /// A chat model behind an OpenAI-compatible API, like OpenAI, Ollama or llama.cpp.
+    Llm,
+    /// A program reading the text on stdin and writing the translation on stdout.
+    Command,
+}
+
+/// One of the translators, picked with [`Backend`].
+pub enum AnyTranslator {
+    Google(GoogleTranslator),
+    Offline(OfflineTranslator),
+    Llm(LlmTranslator),
+    Command(CommandTranslator),
+}
+
+impl Translator for AnyTranslator {
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        match self {
+            AnyTranslator::Google(translator) => {
+                translator.translate(from_lang, to_lang, text).await
+            }
+            AnyTranslator::Offline(translator) => {
+                translator.translate(from_lang, to_lang, text).await
+            }
+            AnyTranslator::Llm(translator) => translator.translate(from_lang, to_lang, text).await,
+            AnyTranslator::Command(translator) => {
+                translator.translate(from_lang, to_lang, text).await
+            }
+        }
+    }
+
+    async fn translate_streaming(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        on_chunk: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<String> {
+        match self {
+            AnyTranslator::Google(translator) => {
+                translator
+                    .translate_streaming(from_lang, to_lang, text, on_chunk)
+                    .await
+            }
+            AnyTranslator::Offline(translator) => {
+                translator
+                    .translate_streaming(from_lang, to_lang, text, on_chunk)
+                    .await
+            }
+            AnyTranslator::Llm(translator) => {
+                translator
+                    .translate_streaming(from_lang, to_lang, text, on_chunk)
+                    .await
+            }
+            AnyTranslator::Command(translator) => {
+                translator
+                    .translate_streaming(from_lang, to_lang, text, on_chunk)
+                    .await
+            }
+        }
+    }
+
+    async fn translate_detailed(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<Translation> {
+        match self {
+            AnyTranslator::Google(translator) => {
+                translator
+                    .translate_detailed(from_lang, to_lang, text)
+                    .await
+            }
+            AnyTranslator::Offline(translator) => {
+                translator
+                    .translate_detailed(from_lang, to_lang, text)
+                    .await
+            }
+            AnyTranslator::Llm(translator) => {
+                translator
+                    .translate_detailed(from_lang, to_lang, text)
+                    .await
+            }
+            AnyTranslator::Command(translator) => {
+                translator
+                    .translate_detailed(from_lang, to_lang, text)
+                    .await
+            }
+        }
+    }
+}
+
+/// Remembers the translations of `T`, so that the daemon can show the same text again instantly.
+pub struct CachedTranslator<T> {
+    inner: T,
+    capacity: usize,
+    cache: Mutex<HashMap<(String, String, String), String>>,
+}
+
+impl<T> CachedTranslator<T> {
+    /// The cache is emptied when it holds more than `capacity` translations.
+    pub fn new(inner: T, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, key: &(String, String, String)) -> Option<String> {
+        self.cache.lock().unwrap().get(key).cloned()
+    }
+
+    fn remember(&self, key: (String, String, String), translation: &str) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.capacity {
+            cache.clear();
+        }
+        cache.insert(key, translation.to_string());
+    }
+}
+
+impl<T: Translator> Translator for CachedTranslator<T> {
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let key = (from_lang.to_string(), to_lang.to_string(), text.to_string());
+        if let Some(translation) = self.cached(&key) {
+            return Ok(translation);
+        }
+
+        let translation = self.inner.translate(from_lang, to_lang, text).await?;
+        self.remember(key, &translation);
+
+        Ok(translation)
+    }
+
+    async fn translate_streaming(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        mut on_chunk: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<String> {
+        let key = (from_lang.to_string(), to_lang.to_string(), text.to_string());
+        if let Some(translation) = self.cached(&key) {
+            on_chunk(&translation);
+            return Ok(translation);
+        }
+
+        let translation = self
+            .inner
+            .translate_streaming(from_lang, to_lang, text, on_chunk)
+            .await?;
+        self.remember(key, &translation);
+
+        Ok(translation)
+    }
+
+    /// Always asks the translator, since the details are not cached, then remembers the
+    /// translation.
+    async fn translate_detailed(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<Translation> {
+        let key = (from_lang.to_string(), to_lang.to_string(), text.to_string());
+        let translation = self
+            .inner
+            .translate_detailed(from_lang, to_lang, text)
+            .await?;
+        self.remember(key, &translation.text);
+
+        Ok(translation)
+    }
 }
 
 pub struct GoogleTranslator {
@@ -21,6 +276,33 @@ impl GoogleTranslator {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Language detected and alternatives of `text` told by the JSON API of Google Translate.
+    async fn details(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<(Option<String>, Vec<String>)> {
+        let response: serde_json::Value = self
+            .client
+            .get("https://translate.googleapis.com/translate_a/single")
+            .query(&[
+                ("client", "gtx"),
+                ("sl", from_lang),
+                ("tl", to_lang),
+                ("dt", "t"),
+                ("dt", "at"),
+                ("q", text),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(parse_details(&response))
+    }
 }
 
 impl Translator for GoogleTranslator {
@@ -32,10 +314,8 @@ impl Translator for GoogleTranslator {
     ) -> anyhow::Result<String> {
         let res = self
             .client
-            .get(&format!(
-                "https://translate.google.com/m?sl={}&tl={}&q={}",
-                from_lang, to_lang, text
-            ))
+            .get("https://translate.google.com/m")
+            .query(&[("sl", from_lang), ("tl", to_lang), ("q", text)])
             .send()
             .await?;
         let page = res.text().await?;
@@ -56,4 +336,1043 @@ impl Translator for GoogleTranslator {
             },
         }
     }
+
+    /// Translates like [`Translator::translate`], with the language detected and the alternatives
+    /// of a text of a single sentence told by the JSON API of Google Translate, which are left out
+    /// if it fails.
+    async fn translate_detailed(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<Translation> {
+        let (translation, details) = tokio::join!(
+            self.translate(from_lang, to_lang, text),
+            self.details(from_lang, to_lang, text)
+        );
+        let translation = translation?;
+        let (detected_lang, alternatives) = details.unwrap_or_default();
+
+        Ok(Translation {
+            detected_lang,
+            alternatives: alternatives
+                .into_iter()
+                .filter(|alternative| *alternative != translation)
+                .collect(),
+            text: translation,
+        })
+    }
+}
+
+/// Language detected and alternatives in a `response` of the JSON API of Google Translate,
+/// `[[[translation, source, ...], ...], _, detected_lang, _, _, [[source, _, [[alternative, ...],
+/// ...], ...], ...], ...]`, with one translation and one source per sentence.
+fn parse_details(response: &serde_json::Value) -> (Option<String>, Vec<String>) {
+    let alternatives = match response[5].as_array() {
+        Some(sentences) if sentences.len() == 1 => sentences[0][2]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|alternative| alternative[0].as_str())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    (response[2].as_str().map(str::to_string), alternatives)
+}
+
+/// Translates with the CTranslate2 models found in `model_dir`, one directory per language pair
+/// named like `en-zh`, e.g. OPUS-MT models converted with `ct2-transformers-converter`.
+///
+/// The models are loaded the first time their language pair is used, and kept loaded by all the
+/// clones of the translator.
+#[derive(Clone)]
+pub struct OfflineTranslator {
+    model_dir: PathBuf,
+    #[cfg(feature = "offline")]
+    models: std::sync::Arc<Mutex<OfflineModels>>,
+}
+
+#[cfg(feature = "offline")]
+type OfflineModel = ct2rs::Translator<ct2rs::tokenizers::auto::Tokenizer>;
+/// Models loaded by language pair.
+#[cfg(feature = "offline")]
+type OfflineModels = HashMap<(String, String), std::sync::Arc<OfflineModel>>;
+
+impl OfflineTranslator {
+    pub fn new(model_dir: PathBuf) -> Self {
+        Self {
+            model_dir,
+            #[cfg(feature = "offline")]
+            models: Default::default(),
+        }
+    }
+
+    /// Directory of the model translating from `from_lang` to `to_lang`.
+    fn model_path(&self, from_lang: &str, to_lang: &str) -> anyhow::Result<PathBuf> {
+        if from_lang == "auto" {
+            return Err(anyhow!(
+                "The offline backend cannot detect the language, give it with --from-lang"
+            ));
+        }
+
+        let path = self.model_dir.join(format!("{}-{}", from_lang, to_lang));
+        if path.is_dir() {
+            Ok(path)
+        } else {
+            Err(anyhow!(
+                "No offline model from `{}` to `{}`, expected it in {}",
+                from_lang,
+                to_lang,
+                path.display()
+            ))
+        }
+    }
+
+    #[cfg(feature = "offline")]
+    fn model(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+    ) -> anyhow::Result<std::sync::Arc<OfflineModel>> {
+        let key = (from_lang.to_string(), to_lang.to_string());
+        if let Some(model) = self.models.lock().unwrap().get(&key) {
+            return Ok(model.clone());
+        }
+
+        let path = self.model_path(from_lang, to_lang)?;
+        let config = ct2rs::Config {
+            device: ct2rs::Device::CPU,
+            ..Default::default()
+        };
+        let model =
+            std::sync::Arc::new(ct2rs::Translator::new(&path, &config).map_err(|err| {
+                anyhow!("Failed to load the model in {}: {}", path.display(), err)
+            })?);
+        self.models.lock().unwrap().insert(key, model.clone());
+
+        Ok(model)
+    }
+}
+
+impl Translator for OfflineTranslator {
+    #[cfg(feature = "offline")]
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        self.translate_streaming(from_lang, to_lang, text, |_| {})
+            .await
+    }
+
+    #[cfg(feature = "offline")]
+    async fn translate_streaming(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        mut on_chunk: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<String> {
+        // Loading and running the model keeps the CPU busy, so it is done on a blocking thread
+        // which sends the lines as soon as they are translated.
+        let translator = self.clone();
+        let (from_lang, to_lang, text) =
+            (from_lang.to_string(), to_lang.to_string(), text.to_string());
+        let (chunks, mut translated_chunks) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || {
+            let model = translator.model(&from_lang, &to_lang)?;
+            let options = ct2rs::TranslationOptions::<String, String>::default();
+            // The models translate sentences, so every line is translated on its own and the line
+            // breaks are kept.
+            for (index, line) in text.lines().enumerate() {
+                let mut chunk = if index == 0 {
+                    String::new()
+                } else {
+                    "\n".to_string()
+                };
+                if !line.trim().is_empty() {
+                    let results = model
+                        .translate_batch(&[line], &options, None)
+                        .map_err(|err| anyhow!("The offline translation failed: {}", err))?;
+                    if let Some((translated, _score)) = results.into_iter().next() {
+                        chunk.push_str(&translated);
+                    }
+                }
+                if chunks.send(chunk).is_err() {
+                    break;
+                }
+            }
+
+            anyhow::Ok(())
+        });
+
+        let mut translation = String::new();
+        while let Some(chunk) = translated_chunks.recv().await {
+            on_chunk(&chunk);
+            translation.push_str(&chunk);
+        }
+        task.await.context("The offline translation panicked")??;
+
+        Ok(translation)
+    }
+
+    #[cfg(not(feature = "offline"))]
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        _text: &str,
+    ) -> anyhow::Result<String> {
+        self.model_path(from_lang, to_lang)?;
+        Err(anyhow!(
+            "Built without offline translation support, enable the `offline` feature"
+        ))
+    }
+}
+
+/// Asks a chat model through the `chat/completions` endpoint of an OpenAI-compatible API.
+pub struct LlmTranslator {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+    temperature: Option<f64>,
+    prompt: String,
+    context: String,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: [ChatMessage<'a>; 2],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    content: String,
+}
+
+/// Piece of a streamed answer, sent as a server-sent event.
+#[derive(Deserialize)]
+struct ChatStreamChunk {
+    choices: Vec<ChatStreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatStreamChoice {
+    #[serde(default)]
+    delta: ChatDelta,
+}
+
+#[derive(Default, Deserialize)]
+struct ChatDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorMessage,
+}
+
+#[derive(Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+impl LlmTranslator {
+    pub fn new(config: LlmConfig) -> anyhow::Result<Self> {
+        let url = config.url.as_deref().unwrap_or(DEFAULT_LLM_URL);
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            endpoint: format!("{}/chat/completions", url.trim_end_matches('/')),
+            api_key: config
+                .api_key
+                .or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+            model: config
+                .model
+                .ok_or_else(|| anyhow!("--llm-model is required with --backend llm"))?,
+            temperature: config.temperature,
+            prompt: config
+                .prompt
+                .unwrap_or_else(|| DEFAULT_LLM_PROMPT.to_string()),
+            context: config.context.unwrap_or_default(),
+        })
+    }
+
+    fn system_prompt(&self, from_lang: &str, to_lang: &str) -> String {
+        let from_lang = match from_lang {
+            "auto" => "its language",
+            from_lang => from_lang,
+        };
+
+        self.prompt
+            .replace("{from}", from_lang)
+            .replace("{to}", to_lang)
+            .replace("{context}", &self.context)
+    }
+
+    /// Sends `text` to translate, the answer is streamed if `stream`.
+    async fn send(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        stream: bool,
+    ) -> anyhow::Result<reqwest::Response> {
+        let system_prompt = self.system_prompt(from_lang, to_lang);
+        let request = ChatRequest {
+            model: &self.model,
+            messages: [
+                ChatMessage {
+                    role: "system",
+                    content: &system_prompt,
+                },
+                ChatMessage {
+                    role: "user",
+                    content: text,
+                },
+            ],
+            temperature: self.temperature,
+            stream,
+        };
+
+        let mut builder = self.client.post(&self.endpoint).json(&request);
+        if let Some(api_key) = &self.api_key {
+            builder = builder.bearer_auth(api_key);
+        }
+        let res = builder
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", self.endpoint))?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<ErrorResponse>(&body)
+                .map(|error| error.error.message)
+                .unwrap_or(body);
+            return Err(anyhow!("The model server answered {}: {}", status, message));
+        }
+
+        Ok(res)
+    }
+}
+
+impl Translator for LlmTranslator {
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let response = self
+            .send(from_lang, to_lang, text, false)
+            .await?
+            .json::<ChatResponse>()
+            .await
+            .context("Unexpected answer from the model server")?;
+        match response.choices.into_iter().next() {
+            Some(choice) => Ok(choice.message.content.trim().to_string()),
+            None => Err(anyhow!("The model server answered no translation")),
+        }
+    }
+
+    async fn translate_streaming(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+        mut on_chunk: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<String> {
+        let mut res = self.send(from_lang, to_lang, text, true).await?;
+
+        let mut translation = String::new();
+        let mut pending = Vec::new();
+        'events: while let Some(bytes) = res
+            .chunk()
+            .await
+            .context("The model server stopped answering")?
+        {
+            pending.extend_from_slice(&bytes);
+            while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+                let line = pending.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let data = match line.trim().strip_prefix("data:") {
+                    Some(data) => data.trim(),
+                    None => continue,
+                };
+                if data == "[DONE]" {
+                    break 'events;
+                }
+
+                let chunk = serde_json::from_str::<ChatStreamChunk>(data)
+                    .context("Unexpected answer from the model server")?;
+                let content = chunk
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.delta.content)
+                    .unwrap_or_default();
+                let content = if translation.is_empty() {
+                    content.trim_start()
+                } else {
+                    &content
+                };
+                if !content.is_empty() {
+                    on_chunk(content);
+                    translation.push_str(content);
+                }
+            }
+        }
+
+        Ok(translation.trim_end().to_string())
+    }
+}
+
+/// Runs a program with the text to translate on stdin, and takes its stdout as the translation.
+pub struct CommandTranslator {
+    program: String,
+    /// `{from}` and `{to}` are replaced by the languages.
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandTranslator {
+    /// `command` is split into words like a shell would, but without running one.
+    pub fn new(command: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let mut words = split_command(command)?.into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow!("The translation command is empty"))?;
+
+        Ok(Self {
+            program,
+            args: words.collect(),
+            timeout,
+        })
+    }
+}
+
+impl Translator for CommandTranslator {
+    async fn translate(
+        &self,
+        from_lang: &str,
+        to_lang: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(
+                self.args
+                    .iter()
+                    .map(|arg| arg.replace("{from}", from_lang).replace("{to}", to_lang)),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.program))?;
+
+        let mut stdin = child.stdin.take().context("Failed to open stdin")?;
+        // Written while the output is read, so that a program answering line by line cannot block
+        // on a full pipe. A program which does not read all of its input is fine too.
+        let write = async move {
+            let _ = stdin.write_all(text.as_bytes()).await;
+        };
+        let output = tokio::time::timeout(self.timeout, async {
+            tokio::join!(write, child.wait_with_output()).1
+        })
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "{} did not finish within {} ms",
+                self.program,
+                self.timeout.as_millis()
+            )
+        })?
+        .with_context(|| format!("Failed to run {}", self.program))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "{} failed ({}): {}",
+                self.program,
+                output.status,
+                stderr.trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    }
+}
+
+/// Splits `command` into words at whitespace, keeping the text quoted with `'` or `"` and the
+/// characters escaped with `\` in a single word.
+fn split_command(command: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("The translation command ends with \\"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quote in the translation command"));
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn google_details() {
+        let response = serde_json::json!([
+            [["Guten Morgen", "Good morning", null, null, 10]],
+            null,
+            "en",
+            null,
+            null,
+            [[
+                "Good morning",
+                null,
+                [["Guten Morgen", 0], ["Morgen", 0]],
+                [[0, 12]],
+                "Good morning"
+            ]]
+        ]);
+
+        assert_eq!(
+            parse_details(&response),
+            (
+                Some("en".to_string()),
+                vec!["Guten Morgen".to_string(), "Morgen".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn google_details_of_sentences() {
+        // The alternatives of the sentences cannot be put together.
+        let response = serde_json::json!([
+            [["Hallo. ", "Hello. "], ["Tschüss.", "Bye."]],
+            null,
+            "en",
+            null,
+            null,
+            [
+                ["Hello.", null, [["Hallo.", 0]]],
+                ["Bye.", null, [["Tschüss.", 0]]]
+            ]
+        ]);
+
+        assert_eq!(
+            parse_details(&response),
+            (Some("en".to_string()), Vec::new())
+        );
+        assert_eq!(parse_details(&serde_json::json!({})), (None, Vec::new()));
+    }
+
+    #[test]
+    fn offline_model_path_needs_the_source_language() {
+        let translator = OfflineTranslator::new(PathBuf::from("/nonexistent"));
+
+        let err = translator.model_path("auto", "de").unwrap_err();
+
+        assert!(err.to_string().contains("--from-lang"), "{}", err);
+    }
+
+    #[test]
+    fn offline_model_path_of_a_missing_pair() {
+        let model_dir = tempfile::tempdir().unwrap();
+        let translator = OfflineTranslator::new(model_dir.path().to_path_buf());
+
+        let err = translator.model_path("en", "de").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "No offline model from `en` to `de`, expected it in {}",
+                model_dir.path().join("en-de").display()
+            )
+        );
+    }
+
+    #[test]
+    fn offline_model_path_of_a_pair() {
+        let model_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(model_dir.path().join("en-de")).unwrap();
+        let translator = OfflineTranslator::new(model_dir.path().to_path_buf());
+
+        assert_eq!(
+            translator.model_path("en", "de").unwrap(),
+            model_dir.path().join("en-de")
+        );
+    }
+
+    #[cfg(not(feature = "offline"))]
+    #[tokio::test]
+    async fn offline_translate_without_the_feature() {
+        let model_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(model_dir.path().join("en-de")).unwrap();
+        let translator = OfflineTranslator::new(model_dir.path().to_path_buf());
+
+        let err = translator.translate("en", "de", "Hello").await.unwrap_err();
+
+        assert!(err.to_string().contains("`offline` feature"), "{}", err);
+    }
+
+    /// Directory of the tiny model made by `tests/fixtures/make-offline-model.sh`, with random
+    /// weights so only the shape of its translations is checked. The tests are skipped without it,
+    /// but in CI where it is made.
+    #[cfg(feature = "offline")]
+    fn offline_fixture() -> Option<PathBuf> {
+        let model_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/offline");
+        if model_dir.join("en-de").is_dir() {
+            return Some(model_dir);
+        }
+
+        let message = format!(
+            "No offline model in {}, make it with tests/fixtures/make-offline-model.sh",
+            model_dir.display()
+        );
+        assert!(std::env::var_os("CI").is_none(), "{}", message);
+        eprintln!("Skipped: {}", message);
+        None
+    }
+
+    #[cfg(feature = "offline")]
+    #[tokio::test]
+    async fn offline_translate_line_by_line() {
+        let Some(model_dir) = offline_fixture() else {
+            return;
+        };
+        let translator = OfflineTranslator::new(model_dir);
+        let mut chunks = Vec::new();
+
+        let translation = translator
+            .translate_streaming("en", "de", "Hello world.\n\nHow are you?", |chunk| {
+                chunks.push(chunk.to_string())
+            })
+            .await
+            .unwrap();
+
+        let lines: Vec<_> = translation.split('\n').collect();
+        assert_eq!(lines.len(), 3, "{:?}", translation);
+        assert!(lines[1].is_empty(), "{:?}", translation);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), translation);
+    }
+
+    #[cfg(feature = "offline")]
+    #[test]
+    fn offline_model_is_loaded_once() {
+        let Some(model_dir) = offline_fixture() else {
+            return;
+        };
+        let translator = OfflineTranslator::new(model_dir);
+
+        let first = translator.model("en", "de").unwrap();
+        let second = translator.clone().model("en", "de").unwrap();
+
+        assert!(std::sync::Arc::ptr_eq(&first, &second));
+    }
+
+    #[cfg(feature = "offline")]
+    #[tokio::test]
+    async fn offline_translate_of_a_missing_pair() {
+        let translator = OfflineTranslator::new(PathBuf::from("/nonexistent"));
+
+        let err = translator.translate("de", "en", "Hallo").await.unwrap_err();
+
+        assert!(err.to_string().starts_with("No offline model"), "{}", err);
+    }
+
+    /// Answers one request on 127.0.0.1 with `status` and a body written in `parts`, flushed one
+    /// by one, and gives back the URL of the server and the head and body of the request.
+    async fn serve_once(
+        status: &'static str,
+        parts: &'static [&'static str],
+    ) -> (String, tokio::task::JoinHandle<(String, serde_json::Value)>) {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let (head, body) = loop {
+                let mut buffer = [0; 4096];
+                let read = stream.read(&mut buffer).await.unwrap();
+                assert!(read > 0, "The request ended early");
+                request.extend_from_slice(&buffer[..read]);
+                let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+                    continue;
+                };
+                let head = String::from_utf8(request[..end].to_vec()).unwrap();
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    break (head, request[end + 4..end + 4 + length].to_vec());
+                }
+            };
+
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            for part in parts {
+                stream.write_all(part.as_bytes()).await.unwrap();
+                stream.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            stream.shutdown().await.unwrap();
+
+            (head, serde_json::from_slice(&body).unwrap())
+        });
+
+        (url, server)
+    }
+
+    fn llm_translator(url: String) -> LlmTranslator {
+        LlmTranslator::new(LlmConfig {
+            url: Some(url),
+            api_key: Some("secret".to_string()),
+            model: Some("tiny".to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn llm_translate() {
+        let (url, server) = serve_once(
+            "200 OK",
+            &[r#"{"choices":[{"message":{"role":"assistant","content":" Bonjour \n"}}]}"#],
+        )
+        .await;
+
+        let translation = llm_translator(url)
+            .translate("en", "fr", "Hello")
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "Bonjour");
+        let (head, request) = server.await.unwrap();
+        assert!(head.starts_with("POST /v1/chat/completions "), "{}", head);
+        assert!(
+            head.to_lowercase().contains("authorization: bearer secret"),
+            "{}",
+            head
+        );
+        assert_eq!(request["model"], "tiny");
+        assert_eq!(request["stream"], false);
+        assert!(request.get("temperature").is_none());
+        assert_eq!(request["messages"][0]["role"], "system");
+        assert_eq!(
+            request["messages"][0]["content"],
+            "You are a translation engine. Translate the text of the user from en to fr. \n\
+             Answer with the translation only, without quotes, notes or explanations."
+        );
+        assert_eq!(request["messages"][1]["role"], "user");
+        assert_eq!(request["messages"][1]["content"], "Hello");
+    }
+
+    #[tokio::test]
+    async fn llm_translate_streaming() {
+        // The events are cut anywhere by the network, the ones after `[DONE]` are ignored.
+        let (url, server) = serve_once(
+            "200 OK",
+            &[
+                ": keep-alive\n\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\" Bon\"}}]}\n\nda",
+                "ta: {\"choices\":[{\"delta\":{\"con",
+                "tent\":\"jour\"}}]}\r\n\r\ndata: {\"choices\":[{\"delta\":{\"content\":\" !\\n\"}}]}\n",
+                "\ndata: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"more\"}}]}\n\n",
+            ],
+        )
+        .await;
+        let mut chunks = Vec::new();
+
+        let translation = llm_translator(url)
+            .translate_streaming("en", "fr", "Hello!", |chunk| chunks.push(chunk.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "Bonjour !");
+        assert_eq!(chunks, ["Bon", "jour", " !\n"]);
+        let (_, request) = server.await.unwrap();
+        assert_eq!(request["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn llm_translate_error() {
+        let (url, _server) = serve_once(
+            "401 Unauthorized",
+            &[r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#],
+        )
+        .await;
+
+        let err = llm_translator(url)
+            .translate("en", "fr", "Hello")
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The model server answered 401 Unauthorized: Incorrect API key provided"
+        );
+    }
+
+    #[tokio::test]
+    async fn llm_translate_error_without_json() {
+        let (url, _server) = serve_once("502 Bad Gateway", &["upstream down"]).await;
+
+        let err = llm_translator(url)
+            .translate_streaming("en", "fr", "Hello", |_| {})
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The model server answered 502 Bad Gateway: upstream down"
+        );
+    }
+
+    #[test]
+    fn llm_system_prompt() {
+        let translator = LlmTranslator::new(LlmConfig {
+            model: Some("tiny".to_string()),
+            prompt: Some("From {from} to {to}, twice {to}. {context}".to_string()),
+            context: Some("Medical text.".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            translator.system_prompt("en", "fr"),
+            "From en to fr, twice fr. Medical text."
+        );
+        assert_eq!(
+            translator.system_prompt("auto", "fr"),
+            "From its language to fr, twice fr. Medical text."
+        );
+    }
+
+    #[test]
+    fn llm_needs_a_model() {
+        let err = LlmTranslator::new(LlmConfig::default()).err().unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "--llm-model is required with --backend llm"
+        );
+    }
+
+    fn command_translator(command: &str) -> CommandTranslator {
+        CommandTranslator::new(command, Duration::from_secs(5)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn command_translate_stdin_to_stdout() {
+        let translation = command_translator("tr a-z A-Z")
+            .translate("en", "fr", "hello\nworld\n")
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "HELLO\nWORLD");
+    }
+
+    #[tokio::test]
+    async fn command_translate_languages() {
+        let translation = command_translator("echo {from}:{to} --lang={to}")
+            .translate("en", "fr", "hello")
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "en:fr --lang=fr");
+    }
+
+    #[tokio::test]
+    async fn command_translate_trims_the_end() {
+        let translation = command_translator("printf '  hello \\n\\n'")
+            .translate("en", "fr", "")
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "  hello");
+    }
+
+    #[tokio::test]
+    async fn command_translate_without_reading_stdin() {
+        // Larger than a pipe, which would block a write that waited for the program to read.
+        let text = "hello ".repeat(100_000);
+
+        let translation = command_translator("echo done")
+            .translate("en", "fr", &text)
+            .await
+            .unwrap();
+
+        assert_eq!(translation, "done");
+    }
+
+    #[tokio::test]
+    async fn command_translate_failure() {
+        let err =
+            command_translator("sh -c 'echo partial; echo \"no model for $0\" >&2; exit 3' {to}")
+                .translate("en", "fr", "hello")
+                .await
+                .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "sh failed (exit status: 3): no model for fr"
+        );
+    }
+
+    #[tokio::test]
+    async fn command_translate_missing_program() {
+        let err = command_translator("/nonexistent/translate")
+            .translate("en", "fr", "hello")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Failed to run /nonexistent/translate");
+    }
+
+    #[tokio::test]
+    async fn command_translate_timeout() {
+        let pid_file = tempfile::NamedTempFile::new().unwrap();
+        let command = format!(
+            "sh -c 'echo $$ > {}; exec sleep 10'",
+            pid_file.path().display()
+        );
+        let translator = CommandTranslator::new(&command, Duration::from_millis(300)).unwrap();
+        let start = std::time::Instant::now();
+
+        let err = translator.translate("en", "fr", "hello").await.unwrap_err();
+
+        assert_eq!(err.to_string(), "sh did not finish within 300 ms");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // The program is killed, and then reaped in the background.
+        let pid = std::fs::read_to_string(pid_file.path()).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let killed = (0..50).any(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            std::fs::read_to_string(&stat).map_or(true, |stat| stat.contains(") Z "))
+        });
+        assert!(killed, "sh is still running");
+    }
+
+    #[test]
+    fn command_needs_a_program() {
+        let err = CommandTranslator::new(" ", Duration::from_secs(5))
+            .err()
+            .unwrap();
+
+        assert_eq!(err.to_string(), "The translation command is empty");
+    }
+
+    #[test]
+    fn split_command_words() {
+        assert_eq!(
+            split_command("  trans -b\t{from}:{to} ").unwrap(),
+            ["trans", "-b", "{from}:{to}"]
+        );
+        assert!(split_command("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_command_quotes() {
+        assert_eq!(
+            split_command(r#"sh -c 'tr a-z A-Z | sed "s/ //"' "two words" '' a"b"'c'"#).unwrap(),
+            [
+                "sh",
+                "-c",
+                r#"tr a-z A-Z | sed "s/ //""#,
+                "two words",
+                "",
+                "abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn split_command_escapes() {
+        assert_eq!(
+            split_command(r#"echo a\ b \"c\" "d\"e\\" 'f\g'"#).unwrap(),
+            ["echo", "a b", r#""c""#, r#"d"e\"#, r"f\g"]
+        );
+    }
+
+    #[test]
+    fn split_command_errors() {
+        for command in ["echo 'hello", r#"echo "hello"#, r#"echo "it's"#] {
+            assert_eq!(
+                split_command(command).unwrap_err().to_string(),
+                "Unterminated quote in the translation command",
+                "{}",
+                command
+            );
+        }
+        assert_eq!(
+            split_command(r"echo \").unwrap_err().to_string(),
+            r"The translation command ends with \"
+        );
+    }
 }
@@ -0,0 +1,76 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use glib::clone;
+use gtk4::prelude::*;
+use gtk4::Application;
+
+use crate::source::watch_primary_selection;
+use crate::window::{is_copied_translation, TranslationWindow, TranslationWindowConfig};
+
+/// Opens a translation window for every new primary selection once it has not changed for
+/// `delay`, until the Wayland connection is lost.
+///
+/// SIGUSR1 pauses and resumes watching, so that it can be toggled with a compositor key binding.
+pub fn start_watching(
+    app: &Application,
+    config: TranslationWindowConfig,
+    delay: Duration,
+    paused: bool,
+) {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+    std::thread::spawn(move || {
+        if let Err(err) = watch_primary_selection(|text| {
+            let _ = sender.send(text);
+        }) {
+            eprintln!("Error: {:#}", err);
+        }
+    });
+
+    let paused = Rc::new(Cell::new(paused));
+    glib::unix_signal_add_local(
+        libc::SIGUSR1,
+        clone!(@strong paused => move || {
+            paused.set(!paused.get());
+            glib::ControlFlow::Continue
+        }),
+    );
+
+    // Keep running without windows until the watcher thread stops.
+    let hold = app.hold();
+    let last_text = Rc::new(RefCell::new(String::new()));
+    let timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+    glib::spawn_future_local(clone!(@weak app => async move {
+        let _hold = hold;
+        while let Some(text) = receiver.recv().await {
+            // Selecting text in a translation window, or copying its translation to the primary
+            // selection, should not open another one.
+            let window_active = app.windows().iter().any(|window| window.is_active());
+            let copied = is_copied_translation(&text);
+            if paused.get() || text.trim().is_empty() || window_active || copied {
+                continue;
+            }
+
+            let source = glib::timeout_add_local_once(
+                delay,
+                clone!(@weak app, @strong config, @strong last_text, @strong timer => move || {
+                    timer.take();
+                    if *last_text.borrow() == text {
+                        return;
+                    }
+                    last_text.replace(text.clone());
+
+                    let window = TranslationWindow::new(&TranslationWindowConfig {
+                        src_text: text,
+                        ..config
+                    });
+                    window.create(&app);
+                }),
+            );
+            if let Some(previous) = timer.replace(Some(source)) {
+                previous.remove();
+            }
+        }
+    }));
+}
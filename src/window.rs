@@ -1,50 +1,217 @@
 use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::OnceLock;
+use std::time::Duration;
 
 use glib::clone;
 use gtk4::prelude::*;
 use gtk4::{Application, ApplicationWindow};
 use gtk4_layer_shell::LayerShell;
+use serde::{Deserialize, Serialize};
 
+use crate::cleanup::{self, CleanupStep};
+use crate::config::Shortcuts;
+use crate::dictionary::{self, Definition};
 use crate::env_info::{collect_env_info, EnvironmentInfo};
-use crate::translator::{GoogleTranslator, Translator};
-
-fn tokio_runtime() -> &'static tokio::runtime::Runtime {
+use crate::markdown;
+use crate::placeholder::Protection;
+use crate::placement::{save_last_used, Placement};
+use crate::size::{Dimensions, WindowSize};
+use crate::translator::{
+    AnyTranslator, CachedTranslator, GoogleTranslator, Translation, Translator,
+};
+
+/// Room taken by the padding, spacing and borders around the two text views.
+const CHROME_WIDTH: i32 = 40;
+const CHROME_HEIGHT: i32 = 60;
+
+pub fn tokio_runtime() -> &'static tokio::runtime::Runtime {
     static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
     RUNTIME.get_or_init(|| {
         tokio::runtime::Runtime::new().expect("Setting up tokio runtime needs to succeed.")
     })
 }
 
-#[derive(Debug, Clone)]
+static TRANSLATOR: OnceLock<CachedTranslator<AnyTranslator>> = OnceLock::new();
+
+/// Shared by all the windows, so that the daemon keeps its HTTP connections and cache warm.
+///
+/// Google Translate unless another translator was set with [`set_translator`] first.
+pub fn translator() -> &'static CachedTranslator<AnyTranslator> {
+    TRANSLATOR
+        .get_or_init(|| CachedTranslator::new(AnyTranslator::Google(GoogleTranslator::new()), 1000))
+}
+
+/// Sets the translator of [`translator`], `false` if it was already set or used.
+pub fn set_translator(translator: AnyTranslator) -> bool {
+    TRANSLATOR
+        .set(CachedTranslator::new(translator, 1000))
+        .is_ok()
+}
+
+/// Translates `text` at once, cleaned up and protected like in the windows of `config`.
+pub async fn translate_text(
+    config: &TranslationWindowConfig,
+    from_lang: &str,
+    to_lang: &str,
+    text: &str,
+) -> anyhow::Result<String> {
+    let protection = Protection::load(config, from_lang, to_lang)?;
+    translate_protected(config, &protection, from_lang, to_lang, text).await
+}
+
+/// Like [`translate_text`], with the `protection` of the languages loaded once for many texts.
+pub async fn translate_protected(
+    config: &TranslationWindowConfig,
+    protection: &Protection,
+    from_lang: &str,
+    to_lang: &str,
+    text: &str,
+) -> anyhow::Result<String> {
+    if config.markdown {
+        return markdown::translate(translator(), from_lang, to_lang, text, protection).await;
+    }
+
+    let text = cleanup::clean(text, &config.cleanup);
+    protection
+        .translate(translator(), from_lang, to_lang, &text)
+        .await
+}
+
+/// Like [`translate_text`], also with the language detected and the alternatives when the
+/// translator tells them, which a Markdown document never has.
+pub async fn translate_text_detailed(
+    config: &TranslationWindowConfig,
+    from_lang: &str,
+    to_lang: &str,
+    text: &str,
+) -> anyhow::Result<Translation> {
+    let protection = Protection::load(config, from_lang, to_lang)?;
+    translate_protected_detailed(config, &protection, from_lang, to_lang, text).await
+}
+
+/// Like [`translate_text_detailed`], with the `protection` of the languages loaded once.
+pub async fn translate_protected_detailed(
+    config: &TranslationWindowConfig,
+    protection: &Protection,
+    from_lang: &str,
+    to_lang: &str,
+    text: &str,
+) -> anyhow::Result<Translation> {
+    if config.markdown {
+        let text = markdown::translate(translator(), from_lang, to_lang, text, protection).await?;
+        return Ok(Translation {
+            text,
+            ..Default::default()
+        });
+    }
+
+    let text = cleanup::clean(text, &config.cleanup);
+    protection
+        .translate_detailed(translator(), from_lang, to_lang, &text)
+        .await
+}
+
+thread_local! {
+    /// Last translation put on the primary selection by the windows.
+    static COPIED_TO_PRIMARY: RefCell<String> = RefCell::default();
+}
+
+/// Whether `text` is the last translation put on the primary selection by the windows, which
+/// `--watch` must not translate again.
+pub fn is_copied_translation(text: &str) -> bool {
+    COPIED_TO_PRIMARY.with_borrow(|copied| !copied.is_empty() && copied.trim() == text.trim())
+}
+
+/// Which selection the translation is copied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CopyTarget {
+    Clipboard,
+    Primary,
+    Both,
+}
+
+/// Everything a window needs, sent as JSON to the daemon by the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranslationWindowConfig {
     pub src_text: String,
+    /// Set when `src_text` was recognized in an image.
+    pub ocr_confidence: Option<i32>,
+    /// Applied to the source text every time it is translated.
+    pub cleanup: Vec<CleanupStep>,
+    /// Where to look single words up, see [`dictionary::lookup`].
+    pub dictionary_dirs: Vec<PathBuf>,
+    /// Where the glossaries are, see [`Glossary::load`].
+    pub glossary_dir: Option<PathBuf>,
+    /// Whether to keep the code, URLs and markup away from the translator, see
+    /// [`crate::placeholder::shield`].
+    pub shield: bool,
+    /// Translates the prose of the source text as Markdown instead of the whole text, without
+    /// cleaning it up, see [`markdown::translate`].
+    pub markdown: bool,
     pub from_lang: String,
     pub to_lang: String,
+    pub placement: Placement,
+    pub cursor_offset: i32,
+    pub size: WindowSize,
+    pub min_size: Dimensions,
+    pub max_size: Dimensions,
+    pub close_on_focus_loss: bool,
+    pub close_on_leave: Option<Duration>,
+    pub close_after: Option<Duration>,
+    pub shortcuts: Shortcuts,
+    pub auto_copy: Option<CopyTarget>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TranslationWindow {
     config: TranslationWindowConfig,
+    window: glib::WeakRef<ApplicationWindow>,
+    from_lang_entry: glib::WeakRef<gtk4::Entry>,
+    to_lang_entry: glib::WeakRef<gtk4::Entry>,
     src_textview: glib::WeakRef<gtk4::TextView>,
     dst_textview: glib::WeakRef<gtk4::TextView>,
     translate_button: glib::WeakRef<gtk4::Button>,
-    sender: RefCell<Option<tokio::sync::mpsc::Sender<anyhow::Result<String>>>>,
+    dictionary_panel: glib::WeakRef<gtk4::ScrolledWindow>,
+    dictionary_textview: glib::WeakRef<gtk4::TextView>,
+    sender: RefCell<Option<tokio::sync::mpsc::UnboundedSender<TranslationUpdate>>>,
+}
+
+/// Progress of a translation, sent from the tokio runtime to the window.
+#[derive(Debug)]
+enum TranslationUpdate {
+    /// Piece of the translation to append to the text shown so far.
+    Chunk(String),
+    /// Translations of the glossary terms found in the source text, sent before the translation.
+    Glossary(Vec<String>),
+    Done(anyhow::Result<String>),
+    /// Dictionary entries of the source text, sent only when it is a single word or short phrase.
+    Definitions(Vec<Definition>),
 }
 
 impl TranslationWindow {
     pub fn new(config: &TranslationWindowConfig) -> Self {
         Self {
             config: config.clone(),
+            window: glib::WeakRef::default(),
+            from_lang_entry: glib::WeakRef::default(),
+            to_lang_entry: glib::WeakRef::default(),
             src_textview: glib::WeakRef::default(),
             dst_textview: glib::WeakRef::default(),
             translate_button: glib::WeakRef::default(),
+            dictionary_panel: glib::WeakRef::default(),
+            dictionary_textview: glib::WeakRef::default(),
             sender: RefCell::new(None),
         }
     }
 
     pub fn create(&self, app: &Application) {
+        let env_info = collect_env_info();
+
         let window = ApplicationWindow::new(app);
+        self.window.set(Some(&window));
         let css_provider = gtk4::CssProvider::new();
         css_provider.load_from_data(
             "window { 
@@ -63,32 +230,117 @@ impl TranslationWindow {
         grid.set_column_spacing(10);
         grid.set_row_spacing(10);
 
+        let (from_lang_entry, to_lang_entry) = self.make_lang_entries();
+        grid.attach(&from_lang_entry, 0, 0, 1, 1);
+        grid.attach(&to_lang_entry, 1, 0, 1, 1);
+
         let src_textview = self.make_src_textview();
-        grid.attach(&src_textview, 0, 0, 1, 4);
+        grid.attach(&src_textview, 0, 1, 1, 4);
 
         let scrolled_dst_textview = self.make_dst_textview();
-        grid.attach(&scrolled_dst_textview, 1, 0, 1, 4);
+        grid.attach(&scrolled_dst_textview, 1, 1, 1, 4);
+
+        let dictionary_panel = self.make_dictionary_panel();
+        grid.attach(&dictionary_panel, 0, 5, 2, 2);
 
         let receiver = self.make_translation_channel();
 
+        let button_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 10);
+        button_box.set_homogeneous(true);
+        grid.attach(&button_box, 0, 7, 2, 1);
+
         let translate_button = self.make_translate_button();
-        grid.attach(&translate_button, 0, 4, 1, 1);
+        button_box.append(&translate_button);
+
+        let copy_button = self.make_copy_button();
+        button_box.append(&copy_button);
+
+        if self.config.close_on_focus_loss
+            || self.config.close_on_leave.is_some()
+            || self.config.close_after.is_some()
+        {
+            let pin_button = Self::make_pin_button();
+            button_box.append(&pin_button);
+            self.setup_auto_dismiss(&window, &pin_button);
+        }
 
         let close_button = Self::make_close_button(&window);
-        grid.attach(&close_button, 1, 4, 1, 1);
+        button_box.append(&close_button);
 
         self.start_one_translation();
 
-        self.start_displaying_translations(receiver);
+        self.start_displaying_translations(receiver, env_info);
 
-        let env_info = collect_env_info();
-        let width = env_info.monitor_width / 4;
-        let height = env_info.monitor_height / 4;
-        setup_floating(&window, env_info, width, height);
+        window.add_controller(self.make_shortcut_controller(&window));
+
+        setup_floating(&window);
+        self.place(&env_info);
 
         window.present();
     }
 
+    /// Sizes the window and moves it into place, again after each translation for
+    /// [`WindowSize::Auto`].
+    fn place(&self, env_info: &EnvironmentInfo) {
+        let window = match self.window.upgrade() {
+            Some(window) => window,
+            None => return,
+        };
+
+        let (width, height) = self.config.size.resolve(
+            env_info.monitor_width,
+            env_info.monitor_height,
+            self.config.min_size,
+            self.config.max_size,
+            |max_width| self.content_size(max_width),
+        );
+        let margins =
+            self.config
+                .placement
+                .margins(env_info, width, height, self.config.cursor_offset);
+        window.set_margin(gtk4_layer_shell::Edge::Top, margins.top);
+        window.set_margin(gtk4_layer_shell::Edge::Right, margins.right);
+        window.set_margin(gtk4_layer_shell::Edge::Bottom, margins.bottom);
+        window.set_margin(gtk4_layer_shell::Edge::Left, margins.left);
+        save_last_used(margins);
+    }
+
+    /// Size of a window showing both texts without scrolling, at most `max_width` wide.
+    fn content_size(&self, max_width: i32) -> (i32, i32) {
+        let column_width = ((max_width - CHROME_WIDTH) / 2).max(1);
+        let (src_width, src_height) = self
+            .src_textview
+            .upgrade()
+            .map(|textview| text_size(&textview, column_width))
+            .unwrap_or_default();
+        let (dst_width, dst_height) = self
+            .dst_textview
+            .upgrade()
+            .map(|textview| text_size(&textview, column_width))
+            .unwrap_or_default();
+        let text_width = src_width.max(dst_width);
+        let text_height = src_height.max(dst_height);
+        let dictionary_size = self
+            .dictionary_panel
+            .upgrade()
+            .filter(|panel| panel.is_visible())
+            .and(self.dictionary_textview.upgrade())
+            .map(|textview| text_size(&textview, (max_width - CHROME_WIDTH).max(1)));
+
+        // The text views take 4 of the homogeneous grid rows, the dictionary 2 when it is shown,
+        // and the language entries and the buttons one each.
+        match dictionary_size {
+            None => (
+                text_width * 2 + CHROME_WIDTH,
+                text_height * 6 / 4 + CHROME_HEIGHT,
+            ),
+            Some((dictionary_width, dictionary_height)) => (
+                (text_width * 2).max(dictionary_width) + CHROME_WIDTH,
+                (text_height * 8 / 4).max(dictionary_height * 8 / 2) + CHROME_HEIGHT,
+            ),
+        }
+    }
+
     fn start_one_translation(&self) {
         let src_textview = match self.src_textview.upgrade() {
             Some(textview) => textview,
@@ -107,9 +359,13 @@ impl TranslationWindow {
             Some(sender) => sender,
             None => return,
         };
-        if sender.is_closed() {
+        if sender.is_closed() || !translate_button.is_sensitive() {
             return;
         }
+        let (from_lang, to_lang) = match self.languages() {
+            Some(languages) => languages,
+            None => return,
+        };
 
         translate_button.set_sensitive(false);
         translate_button.set_label("Translating...");
@@ -121,19 +377,154 @@ impl TranslationWindow {
             &src_textview.buffer().end_iter(),
             false,
         );
-        let from_lang = &self.config.from_lang;
-        let to_lang = &self.config.to_lang;
+        let src_text = if self.config.markdown {
+            src_text.to_string()
+        } else {
+            cleanup::clean(&src_text, &self.config.cleanup)
+        };
 
-        tokio_runtime().spawn(clone!(@strong sender, @strong from_lang, @strong to_lang => async move {
-            let translator = GoogleTranslator::new();
-            let translated = <GoogleTranslator as Translator>::translate(&translator, &from_lang, &to_lang, &src_text).await;
-            sender.send(translated).await.unwrap();
+        if let Some(panel) = self.dictionary_panel.upgrade() {
+            panel.set_visible(false);
+        }
+        if let Some(word) = dictionary::lookup_word(&src_text) {
+            let dirs = self.config.dictionary_dirs.clone();
+            let word = word.to_string();
+            let sender = sender.clone();
+            tokio_runtime().spawn_blocking(move || {
+                let definitions = dictionary::lookup(&dirs, &word);
+                let _ = sender.send(TranslationUpdate::Definitions(definitions));
+            });
+        }
+
+        if self.config.markdown {
+            let config = self.config.clone();
+            tokio_runtime().spawn(clone!(@strong sender => async move {
+                let translated = translate_text(&config, &from_lang, &to_lang, &src_text).await;
+                let _ = sender.send(TranslationUpdate::Done(translated));
+            }));
+            return;
+        }
+
+        let protected = match Protection::load(&self.config, &from_lang, &to_lang) {
+            Ok(protection) => protection.protect(&src_text),
+            Err(err) => {
+                let _ = sender.send(TranslationUpdate::Done(Err(err)));
+                return;
+            }
+        };
+        let _ = sender.send(TranslationUpdate::Glossary(
+            protected.glossary_terms.clone(),
+        ));
+
+        tokio_runtime().spawn(clone!(@strong sender => async move {
+            // The chunks are shown with their placeholders restored, except for a placeholder
+            // cut at the end, which waits for the next chunk.
+            let mut received = String::new();
+            let mut shown = 0;
+            let placeholders = &protected.placeholders;
+            let translated = translator()
+                .translate_streaming(&from_lang, &to_lang, &protected.text, |chunk| {
+                    received.push_str(chunk);
+                    let restored = placeholders.restore_partial(&received);
+                    if restored.len() > shown {
+                        let chunk = restored[shown..].to_string();
+                        let _ = sender.send(TranslationUpdate::Chunk(chunk));
+                        shown = restored.len();
+                    }
+                })
+                .await;
+            let translated = translated.map(|translated| placeholders.restore(&translated));
+            let _ = sender.send(TranslationUpdate::Done(translated));
         }));
     }
 
+    fn languages(&self) -> Option<(String, String)> {
+        let from_lang_entry = self.from_lang_entry.upgrade()?;
+        let to_lang_entry = self.to_lang_entry.upgrade()?;
+
+        Some((
+            from_lang_entry.text().trim().to_string(),
+            to_lang_entry.text().trim().to_string(),
+        ))
+    }
+
+    /// Swaps the languages, and the texts if there is a translation, then translates again.
+    fn swap_languages(&self) {
+        if !self
+            .translate_button
+            .upgrade()
+            .is_some_and(|button| button.is_sensitive())
+        {
+            return;
+        }
+        let from_lang_entry = match self.from_lang_entry.upgrade() {
+            Some(entry) => entry,
+            None => return,
+        };
+        let to_lang_entry = match self.to_lang_entry.upgrade() {
+            Some(entry) => entry,
+            None => return,
+        };
+        let src_textview = match self.src_textview.upgrade() {
+            Some(textview) => textview,
+            None => return,
+        };
+        let dst_textview = match self.dst_textview.upgrade() {
+            Some(textview) => textview,
+            None => return,
+        };
+
+        let from_lang = from_lang_entry.text();
+        from_lang_entry.set_text(&to_lang_entry.text());
+        to_lang_entry.set_text(&from_lang);
+
+        let dst_text = dst_textview.buffer().text(
+            &dst_textview.buffer().start_iter(),
+            &dst_textview.buffer().end_iter(),
+            false,
+        );
+        if !dst_text.is_empty() && !dst_textview.has_css_class("error") {
+            src_textview.buffer().set_text(&dst_text);
+        }
+
+        self.start_one_translation();
+    }
+
+    /// Puts the whole translation on the clipboard and/or the primary selection.
+    fn copy_translation(&self, target: CopyTarget) {
+        let dst_textview = match self.dst_textview.upgrade() {
+            Some(textview) => textview,
+            None => return,
+        };
+        if dst_textview.has_css_class("error") {
+            return;
+        }
+
+        let dst_text = dst_textview.buffer().text(
+            &dst_textview.buffer().start_iter(),
+            &dst_textview.buffer().end_iter(),
+            false,
+        );
+        if dst_text.is_empty() {
+            return;
+        }
+        let app = self
+            .window
+            .upgrade()
+            .and_then(|window| window.application());
+        if matches!(target, CopyTarget::Clipboard | CopyTarget::Both) {
+            set_selection(app.as_ref(), &dst_textview.clipboard(), &dst_text);
+        }
+        if matches!(target, CopyTarget::Primary | CopyTarget::Both) {
+            set_selection(app.as_ref(), &dst_textview.primary_clipboard(), &dst_text);
+            COPIED_TO_PRIMARY.set(dst_text.to_string());
+        }
+    }
+
     fn start_displaying_translations(
         &self,
-        mut receiver: tokio::sync::mpsc::Receiver<anyhow::Result<String>>,
+        mut receiver: tokio::sync::mpsc::UnboundedReceiver<TranslationUpdate>,
+        env_info: EnvironmentInfo,
     ) {
         let dst_textview = match self.dst_textview.upgrade() {
             Some(textview) => textview,
@@ -144,15 +535,41 @@ impl TranslationWindow {
             None => return,
         };
 
+        let translation_window = self.clone();
         glib::spawn_future_local(
             clone!(@weak dst_textview, @weak translate_button => async move {
-                while let Some(translated) = receiver.recv().await {
+                let mut glossary_terms = Vec::new();
+                while let Some(update) = receiver.recv().await {
+                    let translated = match update {
+                        TranslationUpdate::Chunk(chunk) => {
+                            let buffer = dst_textview.buffer();
+                            buffer.insert(&mut buffer.end_iter(), &chunk);
+                            continue;
+                        }
+                        TranslationUpdate::Glossary(terms) => {
+                            glossary_terms = terms;
+                            continue;
+                        }
+                        TranslationUpdate::Done(translated) => translated,
+                        TranslationUpdate::Definitions(definitions) => {
+                            translation_window.show_definitions(&definitions);
+                            if translation_window.config.size == WindowSize::Auto {
+                                translation_window.place(&env_info);
+                            }
+                            continue;
+                        }
+                    };
                     match translated {
                         Ok(translated) => {
                             if translated.is_empty() {
                                 dst_textview.buffer().set_text("No translation found.");
                             } else {
                                 dst_textview.buffer().set_text(&translated);
+                                highlight(&dst_textview.buffer(), &glossary_terms);
+                                glossary_terms.clear();
+                                if let Some(target) = translation_window.config.auto_copy {
+                                    translation_window.copy_translation(target);
+                                }
                             }
                         }
                         Err(err) => {
@@ -163,11 +580,62 @@ impl TranslationWindow {
 
                     translate_button.set_sensitive(true);
                     translate_button.set_label("Translate");
+
+                    if translation_window.config.size == WindowSize::Auto {
+                        translation_window.place(&env_info);
+                    }
                 }
             }),
         );
     }
 
+    /// Shows `definitions` in the dictionary panel, which stays hidden if there are none.
+    fn show_definitions(&self, definitions: &[Definition]) {
+        let (panel, textview) = match (
+            self.dictionary_panel.upgrade(),
+            self.dictionary_textview.upgrade(),
+        ) {
+            (Some(panel), Some(textview)) => (panel, textview),
+            _ => return,
+        };
+
+        let buffer = textview.buffer();
+        buffer.set_text("");
+        let heading = buffer.tag_table().lookup("heading");
+        for (index, definition) in definitions.iter().enumerate() {
+            let mut end = buffer.end_iter();
+            if index > 0 {
+                buffer.insert(&mut end, "\n\n");
+            }
+            let title = format!("{} ({})\n", definition.word, definition.dictionary);
+            match &heading {
+                Some(heading) => buffer.insert_with_tags(&mut end, &title, &[heading]),
+                None => buffer.insert(&mut end, &title),
+            }
+            buffer.insert(&mut end, &definition.text);
+        }
+        panel.set_visible(!definitions.is_empty());
+    }
+
+    fn make_lang_entries(&self) -> (gtk4::Entry, gtk4::Entry) {
+        let from_lang_entry = gtk4::Entry::new();
+        from_lang_entry.set_placeholder_text(Some("From"));
+        from_lang_entry.set_text(&self.config.from_lang);
+        self.from_lang_entry.set(Some(&from_lang_entry));
+
+        let to_lang_entry = gtk4::Entry::new();
+        to_lang_entry.set_placeholder_text(Some("To"));
+        to_lang_entry.set_text(&self.config.to_lang);
+        self.to_lang_entry.set(Some(&to_lang_entry));
+
+        for entry in [&from_lang_entry, &to_lang_entry] {
+            let translation_window = self.clone();
+            entry.connect_activate(move |_| translation_window.start_one_translation());
+        }
+
+        (from_lang_entry, to_lang_entry)
+    }
+
     fn make_src_textview(&self) -> gtk4::ScrolledWindow {
         let scrolled_src_textview = gtk4::ScrolledWindow::new();
         let css_provider = gtk4::CssProvider::new();
@@ -180,6 +648,12 @@ impl TranslationWindow {
         self.src_textview.set(Some(&src_textview));
         scrolled_src_textview.set_child(Some(&src_textview));
         src_textview.buffer().set_text(&self.config.src_text);
+        if let Some(confidence) = self.config.ocr_confidence {
+            src_textview.set_tooltip_text(Some(&format!(
+                "Recognized with {}% confidence, fix the text and translate again if needed",
+                confidence
+            )));
+        }
 
         scrolled_src_textview
     }
@@ -194,6 +668,10 @@ impl TranslationWindow {
         let dst_textview = gtk4::TextView::new();
         dst_textview.set_wrap_mode(gtk4::WrapMode::Word);
         self.dst_textview.set(Some(&dst_textview));
+        dst_textview.buffer().create_tag(
+            Some("glossary"),
+            &[("underline", &gtk4::pango::Underline::Single)],
+        );
         let css_provider = gtk4::CssProvider::new();
         css_provider.load_from_data("textview.error { background-color: #ff0000; }");
         dst_textview
@@ -203,8 +681,29 @@ impl TranslationWindow {
         scrolled_dst_textview
     }
 
-    fn make_translation_channel(&self) -> tokio::sync::mpsc::Receiver<anyhow::Result<String>> {
-        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    fn make_dictionary_panel(&self) -> gtk4::ScrolledWindow {
+        let dictionary_panel = gtk4::ScrolledWindow::new();
+        let css_provider = gtk4::CssProvider::new();
+        css_provider.load_from_data("scrolledwindow { border: 1px solid gray; }");
+        dictionary_panel
+            .style_context()
+            .add_provider(&css_provider, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION);
+        dictionary_panel.set_visible(false);
+        self.dictionary_panel.set(Some(&dictionary_panel));
+
+        let dictionary_textview = gtk4::TextView::new();
+        dictionary_textview.set_wrap_mode(gtk4::WrapMode::Word);
+        dictionary_textview.set_editable(false);
+        dictionary_textview
+            .buffer()
+            .create_tag(Some("heading"), &[("weight", &700)]);
+        self.dictionary_textview.set(Some(&dictionary_textview));
+        dictionary_panel.set_child(Some(&dictionary_textview));
+        dictionary_panel
+    }
+
+    fn make_translation_channel(&self) -> tokio::sync::mpsc::UnboundedReceiver<TranslationUpdate> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
         self.sender.replace(Some(sender));
 
         receiver
@@ -219,6 +718,123 @@ impl TranslationWindow {
         translate_button
     }
 
+    /// Handles the shortcuts before the focused widget, so that e.g. `<Control>Return` does not
+    /// insert a new line first.
+    fn make_shortcut_controller(&self, window: &ApplicationWindow) -> gtk4::ShortcutController {
+        let shortcut_controller = gtk4::ShortcutController::new();
+        shortcut_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let shortcuts = &self.config.shortcuts;
+
+        add_shortcut(
+            &shortcut_controller,
+            &shortcuts.close,
+            clone!(@weak window => @default-return glib::Propagation::Proceed, move |_, _| {
+                window.close();
+                glib::Propagation::Stop
+            }),
+        );
+
+        let translation_window = self.clone();
+        add_shortcut(&shortcut_controller, &shortcuts.translate, move |_, _| {
+            translation_window.start_one_translation();
+            glib::Propagation::Stop
+        });
+
+        let translation_window = self.clone();
+        add_shortcut(
+            &shortcut_controller,
+            &shortcuts.copy,
+            clone!(@weak window => @default-return glib::Propagation::Proceed, move |_, _| {
+                // Let the focused widget copy its selection itself.
+                if window.focus().is_some_and(|focus| has_selection(&focus)) {
+                    return glib::Propagation::Proceed;
+                }
+                translation_window.copy_translation(CopyTarget::Clipboard);
+                glib::Propagation::Stop
+            }),
+        );
+
+        let translation_window = self.clone();
+        add_shortcut(
+            &shortcut_controller,
+            &shortcuts.swap_languages,
+            move |_, _| {
+                translation_window.swap_languages();
+                glib::Propagation::Stop
+            },
+        );
+
+        let from_lang_entry = &self.from_lang_entry;
+        add_shortcut(
+            &shortcut_controller,
+            &shortcuts.focus_languages,
+            clone!(@strong from_lang_entry => move |_, _| {
+                if let Some(from_lang_entry) = from_lang_entry.upgrade() {
+                    from_lang_entry.grab_focus();
+                }
+                glib::Propagation::Stop
+            }),
+        );
+
+        shortcut_controller
+    }
+
+    fn make_copy_button(&self) -> gtk4::Button {
+        let copy_button = gtk4::Button::with_label("Copy");
+        let translation_window = self.clone();
+        copy_button
+            .connect_clicked(move |_| translation_window.copy_translation(CopyTarget::Clipboard));
+
+        copy_button
+    }
+
+    fn make_pin_button() -> gtk4::ToggleButton {
+        let pin_button = gtk4::ToggleButton::with_label("Pin");
+        pin_button.set_tooltip_text(Some("Keep the window open"));
+
+        pin_button
+    }
+
+    /// Closes the window on focus loss, pointer leave or timeout as configured, unless
+    /// `pin_button` is active.
+    fn setup_auto_dismiss(&self, window: &ApplicationWindow, pin_button: &gtk4::ToggleButton) {
+        if self.config.close_on_focus_loss {
+            window.connect_is_active_notify(clone!(@weak pin_button => move |window| {
+                if !window.is_active() && !pin_button.is_active() {
+                    window.close();
+                }
+            }));
+        }
+
+        if let Some(delay) = self.config.close_on_leave {
+            let timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+            let motion_controller = gtk4::EventControllerMotion::new();
+            motion_controller.connect_enter(clone!(@strong timer => move |_, _, _| {
+                cancel_timer(&timer);
+            }));
+            motion_controller.connect_leave(
+                clone!(@weak window, @weak pin_button, @strong timer => move |_| {
+                    if !pin_button.is_active() {
+                        start_close_timer(&window, &timer, delay);
+                    }
+                }),
+            );
+            window.add_controller(motion_controller);
+        }
+
+        if let Some(delay) = self.config.close_after {
+            let timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+            start_close_timer(window, &timer, delay);
+            pin_button.connect_toggled(clone!(@weak window, @strong timer => move |pin_button| {
+                if pin_button.is_active() {
+                    cancel_timer(&timer);
+                } else {
+                    start_close_timer(&window, &timer, delay);
+                }
+            }));
+        }
+    }
+
     fn make_close_button(window: &ApplicationWindow) -> gtk4::Button {
         let close_button = gtk4::Button::with_label("Close");
         close_button.add_css_class("destructive-action");
@@ -230,12 +846,29 @@ impl TranslationWindow {
     }
 }
 
-fn setup_floating(
-    window: &ApplicationWindow,
-    env_info: EnvironmentInfo,
-    max_width: i32,
-    max_height: i32,
-) {
+/// Puts `text` on `clipboard`, and keeps `app` running until another client takes the clipboard
+/// over, since on Wayland the selection is gone once the process which set it exits.
+fn set_selection(app: Option<&Application>, clipboard: &gtk4::gdk::Clipboard, text: &str) {
+    clipboard.set_text(text);
+    let hold = match app {
+        Some(app) => RefCell::new(Some(app.hold())),
+        None => return,
+    };
+
+    let handler = Rc::new(RefCell::new(None));
+    let id = clipboard.connect_changed(clone!(@strong handler => move |clipboard| {
+        if clipboard.is_local() {
+            return;
+        }
+        hold.take();
+        if let Some(id) = handler.take() {
+            clipboard.disconnect(id);
+        }
+    }));
+    handler.replace(Some(id));
+}
+
+fn setup_floating(window: &ApplicationWindow) {
     window.init_layer_shell();
     window.set_anchor(gtk4_layer_shell::Edge::Top, true);
     window.set_anchor(gtk4_layer_shell::Edge::Right, true);
@@ -243,48 +876,85 @@ fn setup_floating(
     window.set_anchor(gtk4_layer_shell::Edge::Bottom, true);
     window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
     window.set_layer(gtk4_layer_shell::Layer::Overlay);
+}
+
+fn add_shortcut(
+    shortcut_controller: &gtk4::ShortcutController,
+    trigger: &str,
+    callback: impl Fn(&gtk4::Widget, Option<&glib::Variant>) -> glib::Propagation + 'static,
+) {
+    if trigger.is_empty() {
+        return;
+    }
+    let trigger = match gtk4::ShortcutTrigger::parse_string(trigger) {
+        Some(trigger) => trigger,
+        None => {
+            eprintln!("Invalid shortcut `{}`, ignoring it", trigger);
+            return;
+        }
+    };
 
-    let (margin_top, margin_right, margin_bottom, margin_left) =
-        calculate_margins(&env_info, max_width, max_height);
-    window.set_margin(gtk4_layer_shell::Edge::Top, margin_top);
-    window.set_margin(gtk4_layer_shell::Edge::Right, margin_right);
-    window.set_margin(gtk4_layer_shell::Edge::Bottom, margin_bottom);
-    window.set_margin(gtk4_layer_shell::Edge::Left, margin_left);
+    shortcut_controller.add_shortcut(gtk4::Shortcut::new(
+        Some(trigger),
+        Some(gtk4::CallbackAction::new(callback)),
+    ));
 }
 
-fn calculate_margins(env_info: &EnvironmentInfo, width: i32, height: i32) -> (i32, i32, i32, i32) {
-    let margin_top;
-    let margin_right;
-    let margin_bottom;
-    let margin_left;
-
-    if env_info.monitor_width - env_info.pointer_x >= width {
-        // Right
-        margin_right = env_info.monitor_width - env_info.pointer_x - width;
-        margin_left = env_info.pointer_x;
-    } else if env_info.pointer_x < width {
-        // Still right but shrink width
-        margin_right = 0;
-        margin_left = env_info.pointer_x;
-    } else {
-        // Left
-        margin_right = env_info.monitor_width - env_info.pointer_x;
-        margin_left = env_info.pointer_x - width;
+fn has_selection(widget: &gtk4::Widget) -> bool {
+    if let Some(textview) = widget.downcast_ref::<gtk4::TextView>() {
+        return textview.buffer().has_selection();
+    }
+    if let Some(editable) = widget.dynamic_cast_ref::<gtk4::Editable>() {
+        return editable.selection_bounds().is_some();
     }
 
-    if env_info.monitor_height - env_info.pointer_y >= height {
-        // Down
-        margin_bottom = env_info.monitor_height - env_info.pointer_y - height;
-        margin_top = env_info.pointer_y;
-    } else if env_info.pointer_y < height {
-        // Still down but shrink height
-        margin_bottom = 0;
-        margin_top = env_info.pointer_y;
-    } else {
-        // Up
-        margin_bottom = env_info.monitor_height - env_info.pointer_y;
-        margin_top = env_info.pointer_y - height;
+    false
+}
+
+/// Closes `window` after `delay`, replacing the timer already in `timer`.
+fn start_close_timer(
+    window: &ApplicationWindow,
+    timer: &Rc<RefCell<Option<glib::SourceId>>>,
+    delay: Duration,
+) {
+    let source = glib::timeout_add_local_once(
+        delay,
+        clone!(@weak window, @strong timer => move || {
+            timer.take();
+            window.close();
+        }),
+    );
+    if let Some(previous) = timer.replace(Some(source)) {
+        previous.remove();
+    }
+}
+
+fn cancel_timer(timer: &RefCell<Option<glib::SourceId>>) {
+    if let Some(source) = timer.take() {
+        source.remove();
     }
+}
 
-    (margin_top, margin_right, margin_bottom, margin_left)
+/// Pixel size of the text in `textview` wrapped at `max_width`.
+fn text_size(textview: &gtk4::TextView, max_width: i32) -> (i32, i32) {
+    let buffer = textview.buffer();
+    let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+    let layout = textview.create_pango_layout(Some(&text));
+    layout.set_wrap(gtk4::pango::WrapMode::WordChar);
+    layout.set_width(max_width * gtk4::pango::SCALE);
+
+    layout.pixel_size()
+}
+
+/// Underlines every occurrence of `terms` in `buffer`.
+fn highlight(buffer: &gtk4::TextBuffer, terms: &[String]) {
+    for term in terms {
+        let mut start = buffer.start_iter();
+        while let Some((match_start, match_end)) =
+            start.forward_search(term, gtk4::TextSearchFlags::TEXT_ONLY, None)
+        {
+            buffer.apply_tag_by_name("glossary", &match_start, &match_end);
+            start = match_end;
+        }
+    }
 }
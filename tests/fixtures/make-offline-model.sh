@@ -0,0 +1,7 @@
+#!/bin/sh
+# Converts a tiny Marian model with random weights into offline/en-de, for the tests of the
+# offline backend. Needs `pip install ctranslate2 transformers sentencepiece torch`.
+set -e
+cd "$(dirname "$0")"
+ct2-transformers-converter --model hf-internal-testing/tiny-random-MarianMTModel \
+  --output_dir offline/en-de --copy_files source.spm target.spm --force